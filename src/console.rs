@@ -0,0 +1,132 @@
+use libc;
+use std::ffi::CString;
+
+use Interop;
+
+pub fn log(s: &str) {
+    js! { (s) b"\
+        console.log(UTF8ToString($0));\
+    \0" };
+}
+
+pub fn info(s: &str) {
+    js! { (s) b"\
+        console.info(UTF8ToString($0));\
+    \0" };
+}
+
+pub fn warn(s: &str) {
+    js! { (s) b"\
+        console.warn(UTF8ToString($0));\
+    \0" };
+}
+
+pub fn error(s: &str) {
+    js! { (s) b"\
+        console.error(UTF8ToString($0));\
+    \0" };
+}
+
+pub fn debug(s: &str) {
+    js! { (s) b"\
+        console.debug(UTF8ToString($0));\
+    \0" };
+}
+
+/// Indents following messages until `group_end`.
+pub fn group(label: &str) {
+    js! { (label) b"\
+        console.group(UTF8ToString($0));\
+    \0" };
+}
+
+pub fn group_collapsed(label: &str) {
+    js! { (label) b"\
+        console.groupCollapsed(UTF8ToString($0));\
+    \0" };
+}
+
+pub fn group_end() {
+    js! { b"\
+        console.groupEnd();\
+    \0" };
+}
+
+/// Starts a timer; `time_end` with the same label logs the elapsed time.
+pub fn time(label: &str) {
+    js! { (label) b"\
+        console.time(UTF8ToString($0));\
+    \0" };
+}
+
+pub fn time_end(label: &str) {
+    js! { (label) b"\
+        console.timeEnd(UTF8ToString($0));\
+    \0" };
+}
+
+/// Logs `rows` as a table with the given column headers.
+pub fn table<R: AsRef<[S]>, S: AsRef<str>>(columns: &[&str], rows: &[R]) {
+    let cols = js! { b"\
+        return WEBPLATFORM.rs_refs.push([]) - 1;\
+    \0" };
+    for column in columns {
+        js! { (cols, *column) b"\
+            WEBPLATFORM.rs_refs[$0].push(UTF8ToString($1));\
+        \0" };
+    }
+    let table = js! { b"\
+        return WEBPLATFORM.rs_refs.push([]) - 1;\
+    \0" };
+    for row in rows {
+        js! { (table) b"\
+            WEBPLATFORM.rs_refs[$0].push({});\
+        \0" };
+        for (i, cell) in row.as_ref().iter().enumerate().take(columns.len()) {
+            js! { (table, cols, i as i32, cell.as_ref()) b"\
+                var rows = WEBPLATFORM.rs_refs[$0];\
+                rows[rows.length - 1][WEBPLATFORM.rs_refs[$1][$2]] = UTF8ToString($3);\
+            \0" };
+        }
+    }
+    js! { (table, cols) b"\
+        console.table(WEBPLATFORM.rs_refs[$0], WEBPLATFORM.rs_refs[$1]);\
+    \0" };
+}
+
+#[cfg(feature = "log")]
+pub use self::logger::{ConsoleLogger, init_logger};
+
+#[cfg(feature = "log")]
+mod logger {
+    use log::{Log, Level, LevelFilter, Metadata, Record, SetLoggerError, set_logger, set_max_level};
+
+    /// A `log` backend writing each record to the console method for its
+    /// level, prefixed with the module it came from.
+    pub struct ConsoleLogger;
+
+    static LOGGER: ConsoleLogger = ConsoleLogger;
+
+    impl Log for ConsoleLogger {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            let msg = format!("[{}] {}", record.module_path().unwrap_or(record.target()), record.args());
+            match record.level() {
+                Level::Error => super::error(&msg),
+                Level::Warn => super::warn(&msg),
+                Level::Info => super::info(&msg),
+                Level::Debug | Level::Trace => super::debug(&msg),
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// Installs `ConsoleLogger` as the global logger, showing records up to `level`.
+    pub fn init_logger(level: LevelFilter) -> Result<(), SetLoggerError> {
+        set_logger(&LOGGER).map(|()| set_max_level(level))
+    }
+}
@@ -0,0 +1,203 @@
+use libc;
+use std::ffi::{CString, CStr};
+use std::str;
+use std::borrow::ToOwned;
+use std::collections::HashMap;
+
+use {Interop, Document, percent_decode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+/// A cookie to be written with `Document::cookie_set`. Names and values are percent-encoded,
+/// and decoded again by `Document::cookies` and `Document::cookie_get`; `;` and control
+/// characters are stripped from `expires`, `path` and `domain`.
+#[derive(Debug, Clone)]
+pub struct CookieBuilder {
+    name: String,
+    value: String,
+    expires: Option<String>,
+    max_age: Option<i32>,
+    path: Option<String>,
+    domain: Option<String>,
+    secure: bool,
+    same_site: Option<SameSite>,
+}
+
+fn encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b == b'-' || b == b'.' || b == b'_' || b == b'~' {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+// Attribute values are written as given, so drop anything that would end the
+// attribute and start another one.
+fn attribute(s: &str) -> String {
+    s.chars().filter(|&c| c != ';' && !c.is_control()).collect()
+}
+
+impl CookieBuilder {
+    pub fn new(name: &str, value: &str) -> CookieBuilder {
+        CookieBuilder {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            expires: None,
+            max_age: None,
+            path: None,
+            domain: None,
+            secure: false,
+            same_site: None,
+        }
+    }
+
+    /// An HTTP date, e.g. `Wed, 21 Oct 2026 07:28:00 GMT`.
+    pub fn expires(mut self, date: &str) -> CookieBuilder {
+        self.expires = Some(attribute(date));
+        self
+    }
+
+    /// Lifetime in seconds; takes precedence over `expires`.
+    pub fn max_age(mut self, seconds: i32) -> CookieBuilder {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    pub fn path(mut self, path: &str) -> CookieBuilder {
+        self.path = Some(attribute(path));
+        self
+    }
+
+    pub fn domain(mut self, domain: &str) -> CookieBuilder {
+        self.domain = Some(attribute(domain));
+        self
+    }
+
+    pub fn secure(mut self, secure: bool) -> CookieBuilder {
+        self.secure = secure;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> CookieBuilder {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    fn to_cookie_string(&self) -> String {
+        let mut cookie = format!("{}={}", encode(&self.name), encode(&self.value));
+        if let Some(ref expires) = self.expires {
+            cookie.push_str(&format!("; expires={}", expires));
+        }
+        if let Some(max_age) = self.max_age {
+            cookie.push_str(&format!("; max-age={}", max_age));
+        }
+        if let Some(ref path) = self.path {
+            cookie.push_str(&format!("; path={}", path));
+        }
+        if let Some(ref domain) = self.domain {
+            cookie.push_str(&format!("; domain={}", domain));
+        }
+        if self.secure {
+            cookie.push_str("; secure");
+        }
+        match self.same_site {
+            Some(SameSite::Strict) => cookie.push_str("; samesite=strict"),
+            Some(SameSite::Lax) => cookie.push_str("; samesite=lax"),
+            Some(SameSite::None) => cookie.push_str("; samesite=none"),
+            None => {}
+        }
+        cookie
+    }
+}
+
+impl<'a> Document<'a> {
+    /// All cookies visible to the page, decoded. If several share a name,
+    /// the one listed first, which has the most specific path, is kept.
+    pub fn cookies(&self) -> HashMap<String, String> {
+        let a = js! { b"\
+            return allocate(intArrayFromString(document.cookie), 'i8', ALLOC_STACK);\
+        \0" };
+        let raw = unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        };
+        let mut cookies = HashMap::new();
+        for pair in raw.split(';') {
+            let pair = pair.trim();
+            if pair.is_empty() {
+                continue;
+            }
+            let (name, value) = match pair.find('=') {
+                Some(i) => (percent_decode(&pair[..i]), percent_decode(&pair[i + 1..])),
+                None => (String::new(), percent_decode(pair)),
+            };
+            cookies.entry(name).or_insert(value);
+        }
+        cookies
+    }
+
+    pub fn cookie_set(&self, cookie: &CookieBuilder) {
+        js! { (&cookie.to_cookie_string()[..]) b"\
+            document.cookie = UTF8ToString($0);\
+        \0" };
+    }
+
+    pub fn cookie_get(&self, name: &str) -> Option<String> {
+        self.cookies().remove(name)
+    }
+
+    /// `path` and `domain` must match those the cookie was set with.
+    pub fn cookie_remove(&self, name: &str, path: Option<&str>, domain: Option<&str>) {
+        let mut cookie = CookieBuilder::new(name, "")
+            .expires("Thu, 01 Jan 1970 00:00:00 GMT")
+            .max_age(0);
+        if let Some(path) = path {
+            cookie = cookie.path(path);
+        }
+        if let Some(domain) = domain {
+            cookie = cookie.domain(domain);
+        }
+        self.cookie_set(&cookie);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode, CookieBuilder};
+    use percent_decode;
+
+    #[test]
+    fn encode_escapes_all_but_unreserved() {
+        assert_eq!(encode("a-Z_0.~"), "a-Z_0.~");
+        assert_eq!(encode("a b;c=d"), "a%20b%3Bc%3Dd");
+        assert_eq!(encode("é"), "%C3%A9");
+    }
+
+    #[test]
+    fn decode_reverses_encode() {
+        for s in &["", "plain", "a b;c=d,e", "é✓", "100%"] {
+            assert_eq!(percent_decode(&encode(s)), *s);
+        }
+    }
+
+    #[test]
+    fn decode_leaves_malformed_escapes() {
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+        assert_eq!(percent_decode("%41%42"), "AB");
+    }
+
+    #[test]
+    fn attributes_cannot_inject_more_attributes() {
+        let cookie = CookieBuilder::new("id", "1").path("/; domain=evil.com").domain("a.com\r\n");
+        assert_eq!(cookie.to_cookie_string(), "id=1; path=/ domain=evil.com; domain=a.com");
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt;
+
+/// A value that can be written to an element's style.
+pub trait CssValue {
+    fn to_css(&self) -> String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Px(f64),
+    Em(f64),
+    Rem(f64),
+    Percent(f64),
+    Vw(f64),
+    Vh(f64),
+    Auto,
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Length::Px(v) => write!(f, "{}px", v),
+            Length::Em(v) => write!(f, "{}em", v),
+            Length::Rem(v) => write!(f, "{}rem", v),
+            Length::Percent(v) => write!(f, "{}%", v),
+            Length::Vw(v) => write!(f, "{}vw", v),
+            Length::Vh(v) => write!(f, "{}vh", v),
+            Length::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Color {
+    Rgb(u8, u8, u8),
+    Rgba(u8, u8, u8, f64),
+    /// Hue in degrees, saturation and lightness in percent.
+    Hsl(f64, f64, f64),
+    Hsla(f64, f64, f64, f64),
+    Named(String),
+}
+
+impl Color {
+    /// `Color::hex(0xff8800)` is `rgb(255, 136, 0)`.
+    pub fn hex(v: u32) -> Color {
+        Color::Rgb((v >> 16) as u8, (v >> 8) as u8, v as u8)
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Color::Rgb(r, g, b) => write!(f, "rgb({}, {}, {})", r, g, b),
+            Color::Rgba(r, g, b, a) => write!(f, "rgba({}, {}, {}, {})", r, g, b, a),
+            Color::Hsl(h, s, l) => write!(f, "hsl({}, {}%, {}%)", h, s, l),
+            Color::Hsla(h, s, l, a) => write!(f, "hsla({}, {}%, {}%, {})", h, s, l, a),
+            Color::Named(ref name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A list of transform functions, applied in the order they're added.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Transform {
+    parts: Vec<String>,
+}
+
+impl Transform {
+    pub fn new() -> Transform {
+        Transform {
+            parts: Vec::new(),
+        }
+    }
+
+    pub fn translate(mut self, x: Length, y: Length) -> Transform {
+        self.parts.push(format!("translate({}, {})", x, y));
+        self
+    }
+
+    pub fn translate_x(mut self, x: Length) -> Transform {
+        self.parts.push(format!("translateX({})", x));
+        self
+    }
+
+    pub fn translate_y(mut self, y: Length) -> Transform {
+        self.parts.push(format!("translateY({})", y));
+        self
+    }
+
+    pub fn scale(mut self, x: f64, y: f64) -> Transform {
+        self.parts.push(format!("scale({}, {})", x, y));
+        self
+    }
+
+    pub fn rotate_deg(mut self, deg: f64) -> Transform {
+        self.parts.push(format!("rotate({}deg)", deg));
+        self
+    }
+
+    pub fn skew_deg(mut self, x: f64, y: f64) -> Transform {
+        self.parts.push(format!("skew({}deg, {}deg)", x, y));
+        self
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.parts.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", self.parts.join(" "))
+        }
+    }
+}
+
+impl CssValue for Length {
+    fn to_css(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl CssValue for Color {
+    fn to_css(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl CssValue for Transform {
+    fn to_css(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl<'a> CssValue for &'a str {
+    fn to_css(&self) -> String {
+        (*self).to_owned()
+    }
+}
+
+impl CssValue for String {
+    fn to_css(&self) -> String {
+        self.clone()
+    }
+}
+
+/// Unitless numbers, e.g. for `opacity` or `z-index`.
+impl CssValue for f64 {
+    fn to_css(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl CssValue for i32 {
+    fn to_css(&self) -> String {
+        self.to_string()
+    }
+}
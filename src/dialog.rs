@@ -0,0 +1,84 @@
+use libc;
+use std::ffi::{CString, CStr};
+use std::str;
+use std::borrow::ToOwned;
+use std::ops::Deref;
+
+use {Interop, HtmlNode, FromNode, listen};
+
+/// A `<dialog>` element, obtained with `HtmlNode::dyn_into`.
+pub struct DialogElement<'a> {
+    node: HtmlNode<'a>,
+}
+
+impl<'a> FromNode<'a> for DialogElement<'a> {
+    fn interface() -> &'static str {
+        "HTMLDialogElement"
+    }
+
+    fn from_node(node: HtmlNode<'a>) -> DialogElement<'a> {
+        DialogElement {
+            node: node,
+        }
+    }
+}
+
+impl<'a> Deref for DialogElement<'a> {
+    type Target = HtmlNode<'a>;
+
+    fn deref(&self) -> &HtmlNode<'a> {
+        &self.node
+    }
+}
+
+impl<'a> DialogElement<'a> {
+    pub fn into_node(self) -> HtmlNode<'a> {
+        self.node
+    }
+
+    pub fn open(&self) -> bool {
+        self.node.prop_get_i32("open") != 0
+    }
+
+    pub fn show(&self) {
+        js! { (self.node.id) b"\
+            WEBPLATFORM.rs_refs[$0].show();\
+        \0" };
+    }
+
+    /// Shows the dialog above the page, blocking interaction with the rest of it.
+    pub fn show_modal(&self) {
+        js! { (self.node.id) b"\
+            WEBPLATFORM.rs_refs[$0].showModal();\
+        \0" };
+    }
+
+    pub fn close(&self, return_value: Option<&str>) {
+        js! { (self.node.id, return_value.unwrap_or(""), return_value.is_some() as i32) b"\
+            var dialog = WEBPLATFORM.rs_refs[$0];\
+            if ($2) {\
+                dialog.close(UTF8ToString($1));\
+            } else {\
+                dialog.close();\
+            }\
+        \0" };
+    }
+
+    pub fn return_value(&self) -> String {
+        self.node.prop_get_str("returnValue")
+    }
+
+    /// Calls `f` with the return value whenever the dialog closes, whether
+    /// through `close`, a `<form method=dialog>` or the Escape key.
+    pub fn on_close<F: FnMut(String) + 'a>(&self, mut f: F) {
+        let id = self.node.id;
+        listen(self.node.doc, id, "close", false, move |_| {
+            let a = js! { (id) b"\
+                return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].returnValue), 'i8', ALLOC_STACK);\
+            \0" };
+            f(unsafe {
+                str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+            });
+        });
+    }
+}
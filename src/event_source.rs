@@ -0,0 +1,129 @@
+use libc;
+use std::ffi::{CString, CStr};
+use std::str;
+use std::borrow::ToOwned;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use {Interop, Document, listen, exception_message};
+
+pub struct EventSource<'a> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+    last_event_id: Rc<RefCell<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerEvent {
+    pub event_type: String,
+    pub data: String,
+    pub last_event_id: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventSourceState {
+    Connecting,
+    Open,
+    Closed,
+}
+
+impl<'a> EventSource<'a> {
+    /// Fails if the browser rejects the URL.
+    pub fn connect(doc: &Document<'a>, url: &str, with_credentials: bool) -> Result<EventSource<'a>, String> {
+        let id = js! { (url, with_credentials as i32) b"\
+            try {\
+                var es = new EventSource(UTF8ToString($0), { withCredentials: !!$1 });\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return WEBPLATFORM.rs_refs.push(es) - 1;\
+        \0" };
+
+        if id < 0 {
+            Err(exception_message(id))
+        } else {
+            Ok(EventSource {
+                id: id,
+                doc: doc,
+                last_event_id: Rc::new(RefCell::new(String::new())),
+            })
+        }
+    }
+
+    /// `Connecting` after an error means the browser is reconnecting;
+    /// `Closed` means it gave up or `close` was called.
+    pub fn ready_state(&self) -> EventSourceState {
+        let state = js! { (self.id) b"\
+            return WEBPLATFORM.rs_refs[$0].readyState;\
+        \0" };
+        match state {
+            0 => EventSourceState::Connecting,
+            1 => EventSourceState::Open,
+            _ => EventSourceState::Closed,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        let a = js! { (self.id) b"\
+            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].url), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    /// The id of the last event delivered to one of this source's handlers.
+    pub fn last_event_id(&self) -> String {
+        self.last_event_id.borrow().clone()
+    }
+
+    /// Subscribes to events sent with `event: <name>`.
+    pub fn on<F: FnMut(ServerEvent) + 'a>(&self, name: &str, mut f: F) {
+        let last_event_id = self.last_event_id.clone();
+        listen(self.doc, self.id, name, false, move |_| {
+            let a = js! { b"\
+                return allocate(intArrayFromString(WEBPLATFORM.event.type), 'i8', ALLOC_STACK);\
+            \0" };
+            let event_type = unsafe {
+                str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+            };
+            let a = js! { b"\
+                return allocate(intArrayFromString(WEBPLATFORM.event.data), 'i8', ALLOC_STACK);\
+            \0" };
+            let data = unsafe {
+                str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+            };
+            let a = js! { b"\
+                return allocate(intArrayFromString(WEBPLATFORM.event.lastEventId), 'i8', ALLOC_STACK);\
+            \0" };
+            let id = unsafe {
+                str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+            };
+            *last_event_id.borrow_mut() = id.clone();
+            f(ServerEvent {
+                event_type: event_type,
+                data: data,
+                last_event_id: id,
+            });
+        });
+    }
+
+    /// Subscribes to events sent without an event name.
+    pub fn on_message<F: FnMut(ServerEvent) + 'a>(&self, f: F) {
+        self.on("message", f);
+    }
+
+    pub fn on_open<F: FnMut() + 'a>(&self, mut f: F) {
+        listen(self.doc, self.id, "open", false, move |_| f());
+    }
+
+    pub fn on_error<F: FnMut() + 'a>(&self, mut f: F) {
+        listen(self.doc, self.id, "error", false, move |_| f());
+    }
+
+    pub fn close(&self) {
+        js! { (self.id) b"\
+            WEBPLATFORM.rs_refs[$0].close();\
+        \0" };
+    }
+}
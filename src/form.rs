@@ -0,0 +1,380 @@
+use libc;
+use std::ffi::CString;
+use std::ops::Deref;
+#[cfg(feature = "serde-form")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde-form")]
+use serde_urlencoded;
+
+use {Interop, HtmlNode, FromNode, Event, listen, string_list};
+
+impl<'a> HtmlNode<'a> {
+    /// Serializes a `<form>`'s successful controls as (name, value) pairs in
+    /// document order, the way the browser would submit them: disabled and
+    /// unnamed controls, buttons, file inputs and unchecked checkboxes and
+    /// radios are skipped, and a multi-select contributes one pair per
+    /// selected option.
+    pub fn form_data(&self) -> Vec<(String, String)> {
+        let pairs = js! { (self.id) b"\
+            var elements = WEBPLATFORM.rs_refs[$0].elements;\
+            var names = [];\
+            var values = [];\
+            for (var i = 0; i < elements.length; i++) {\
+                var el = elements[i];\
+                var type = (el.type || '').toLowerCase();\
+                if (!el.name || el.disabled || el.matches(':disabled')) continue;\
+                if (/^(submit|button|reset|image|file)$/.test(type) || el.tagName == 'BUTTON' || el.tagName == 'FIELDSET') continue;\
+                if ((type == 'checkbox' || type == 'radio') && !el.checked) continue;\
+                if (el.tagName == 'SELECT') {\
+                    for (var j = 0; j < el.options.length; j++) {\
+                        if (el.options[j].selected && !el.options[j].disabled) {\
+                            names.push(el.name);\
+                            values.push(el.options[j].value);\
+                        }\
+                    }\
+                    continue;\
+                }\
+                names.push(el.name);\
+                values.push(el.value);\
+            }\
+            return WEBPLATFORM.rs_refs.push([names, values]) - 1;\
+        \0" };
+        let names = js! { (pairs) b"\
+            return WEBPLATFORM.rs_refs.push(WEBPLATFORM.rs_refs[$0][0]) - 1;\
+        \0" };
+        let values = js! { (pairs) b"\
+            return WEBPLATFORM.rs_refs.push(WEBPLATFORM.rs_refs[$0][1]) - 1;\
+        \0" };
+        string_list(names).into_iter().zip(string_list(values)).collect()
+    }
+
+    /// Restores a `<form>`'s controls to their initial values.
+    pub fn form_reset(&self) {
+        js! { (self.id) b"\
+            WEBPLATFORM.rs_refs[$0].reset();\
+        \0" };
+    }
+
+    /// Submits a `<form>` as if a submit button were clicked: validation runs
+    /// and `submit` listeners fire, unlike the bare `submit()` method.
+    pub fn request_submit(&self) {
+        js! { (self.id) b"\
+            var form = WEBPLATFORM.rs_refs[$0];\
+            if (form.requestSubmit) {\
+                form.requestSubmit();\
+                return;\
+            }\
+            var button = document.createElement('input');\
+            button.type = 'submit';\
+            button.hidden = true;\
+            form.appendChild(button);\
+            button.click();\
+            form.removeChild(button);\
+        \0" };
+    }
+}
+
+#[cfg(feature = "serde-form")]
+impl<'a> HtmlNode<'a> {
+    /// Deserializes `form_data` into `T`, e.g. a `#[derive(Deserialize)]`
+    /// struct with one field per control name. Values are strings, parsed
+    /// into numbers and bools as the field types ask; a name that appears
+    /// more than once, like a multi-select, is an error.
+    pub fn form_deserialize<T: DeserializeOwned>(&self) -> Result<T, String> {
+        let encoded = serde_urlencoded::to_string(self.form_data()).map_err(|e| e.to_string())?;
+        serde_urlencoded::from_str(&encoded).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ValidityState {
+    pub value_missing: bool,
+    pub type_mismatch: bool,
+    pub pattern_mismatch: bool,
+    pub too_long: bool,
+    pub too_short: bool,
+    pub range_underflow: bool,
+    pub range_overflow: bool,
+    pub step_mismatch: bool,
+    pub bad_input: bool,
+    pub custom_error: bool,
+    pub valid: bool,
+}
+
+impl<'a> HtmlNode<'a> {
+    /// Whether a form or form control satisfies its constraints. Invalid
+    /// controls fire `invalid` events.
+    pub fn check_validity(&self) -> bool {
+        let valid = js! { (self.id) b"\
+            return +(WEBPLATFORM.rs_refs[$0].checkValidity());\
+        \0" };
+        valid != 0
+    }
+
+    /// Like `check_validity`, but also shows the browser's validation messages.
+    pub fn report_validity(&self) -> bool {
+        let valid = js! { (self.id) b"\
+            return +(WEBPLATFORM.rs_refs[$0].reportValidity());\
+        \0" };
+        valid != 0
+    }
+
+    /// Marks a form control invalid with `msg`; an empty message clears it.
+    pub fn set_custom_validity(&self, msg: &str) {
+        js! { (self.id, msg) b"\
+            WEBPLATFORM.rs_refs[$0].setCustomValidity(UTF8ToString($1));\
+        \0" };
+    }
+
+    pub fn validation_message(&self) -> String {
+        self.prop_get_str("validationMessage")
+    }
+
+    pub fn validity(&self) -> ValidityState {
+        let bits = js! { (self.id) b"\
+            var v = WEBPLATFORM.rs_refs[$0].validity;\
+            return v.valueMissing | v.typeMismatch << 1 | v.patternMismatch << 2 |\
+                v.tooLong << 3 | v.tooShort << 4 | v.rangeUnderflow << 5 |\
+                v.rangeOverflow << 6 | v.stepMismatch << 7 | v.badInput << 8 |\
+                v.customError << 9 | v.valid << 10;\
+        \0" };
+        ValidityState {
+            value_missing: bits & (1 << 0) != 0,
+            type_mismatch: bits & (1 << 1) != 0,
+            pattern_mismatch: bits & (1 << 2) != 0,
+            too_long: bits & (1 << 3) != 0,
+            too_short: bits & (1 << 4) != 0,
+            range_underflow: bits & (1 << 5) != 0,
+            range_overflow: bits & (1 << 6) != 0,
+            step_mismatch: bits & (1 << 7) != 0,
+            bad_input: bits & (1 << 8) != 0,
+            custom_error: bits & (1 << 9) != 0,
+            valid: bits & (1 << 10) != 0,
+        }
+    }
+
+    /// `invalid` doesn't bubble, so this listens in the capture phase; on a
+    /// `<form>` it catches the events of every control inside it.
+    pub fn on_invalid<F: FnMut(Event<'a>) + 'a>(&self, f: F) {
+        listen(self.doc, self.id, "invalid", true, f);
+    }
+}
+
+pub struct InputElement<'a> {
+    node: HtmlNode<'a>,
+}
+
+pub struct SelectElement<'a> {
+    node: HtmlNode<'a>,
+}
+
+pub struct TextAreaElement<'a> {
+    node: HtmlNode<'a>,
+}
+
+impl<'a> FromNode<'a> for InputElement<'a> {
+    fn interface() -> &'static str {
+        "HTMLInputElement"
+    }
+
+    fn from_node(node: HtmlNode<'a>) -> InputElement<'a> {
+        InputElement {
+            node: node,
+        }
+    }
+}
+
+impl<'a> FromNode<'a> for SelectElement<'a> {
+    fn interface() -> &'static str {
+        "HTMLSelectElement"
+    }
+
+    fn from_node(node: HtmlNode<'a>) -> SelectElement<'a> {
+        SelectElement {
+            node: node,
+        }
+    }
+}
+
+impl<'a> FromNode<'a> for TextAreaElement<'a> {
+    fn interface() -> &'static str {
+        "HTMLTextAreaElement"
+    }
+
+    fn from_node(node: HtmlNode<'a>) -> TextAreaElement<'a> {
+        TextAreaElement {
+            node: node,
+        }
+    }
+}
+
+impl<'a> Deref for InputElement<'a> {
+    type Target = HtmlNode<'a>;
+
+    fn deref(&self) -> &HtmlNode<'a> {
+        &self.node
+    }
+}
+
+impl<'a> Deref for SelectElement<'a> {
+    type Target = HtmlNode<'a>;
+
+    fn deref(&self) -> &HtmlNode<'a> {
+        &self.node
+    }
+}
+
+impl<'a> Deref for TextAreaElement<'a> {
+    type Target = HtmlNode<'a>;
+
+    fn deref(&self) -> &HtmlNode<'a> {
+        &self.node
+    }
+}
+
+fn selection_range(id: libc::c_int) -> Option<(i32, i32)> {
+    let start = js! { (id) b"\
+        try {\
+            var start = WEBPLATFORM.rs_refs[$0].selectionStart;\
+        } catch (e) {\
+            return -1;\
+        }\
+        return start == null ? -1 : start;\
+    \0" };
+    if start < 0 {
+        return None;
+    }
+    let end = js! { (id) b"\
+        return WEBPLATFORM.rs_refs[$0].selectionEnd;\
+    \0" };
+    Some((start, end))
+}
+
+fn selection_range_set(id: libc::c_int, start: i32, end: i32) {
+    js! { (id, start, end) b"\
+        WEBPLATFORM.rs_refs[$0].setSelectionRange($1, $2);\
+    \0" };
+}
+
+impl<'a> InputElement<'a> {
+    pub fn into_node(self) -> HtmlNode<'a> {
+        self.node
+    }
+
+    pub fn value(&self) -> String {
+        self.node.prop_get_str("value")
+    }
+
+    pub fn value_set(&self, v: &str) {
+        self.node.prop_set_str("value", v);
+    }
+
+    pub fn checked(&self) -> bool {
+        self.node.prop_get_i32("checked") != 0
+    }
+
+    pub fn checked_set(&self, v: bool) {
+        self.node.prop_set_i32("checked", v as i32);
+    }
+
+    /// The `type` of the input, lowercased, e.g. `text` or `checkbox`.
+    pub fn input_type(&self) -> String {
+        self.node.prop_get_str("type")
+    }
+
+    /// `None` for input types without a text selection, like checkboxes.
+    pub fn selection_range(&self) -> Option<(i32, i32)> {
+        selection_range(self.node.id)
+    }
+
+    pub fn selection_range_set(&self, start: i32, end: i32) {
+        selection_range_set(self.node.id, start, end);
+    }
+
+    pub fn select(&self) {
+        js! { (self.node.id) b"\
+            WEBPLATFORM.rs_refs[$0].select();\
+        \0" };
+    }
+}
+
+impl<'a> SelectElement<'a> {
+    pub fn into_node(self) -> HtmlNode<'a> {
+        self.node
+    }
+
+    /// The value of the first selected option, empty if none is selected.
+    pub fn value(&self) -> String {
+        self.node.prop_get_str("value")
+    }
+
+    pub fn value_set(&self, v: &str) {
+        self.node.prop_set_str("value", v);
+    }
+
+    /// -1 if no option is selected.
+    pub fn selected_index(&self) -> i32 {
+        self.node.prop_get_i32("selectedIndex")
+    }
+
+    pub fn selected_index_set(&self, index: i32) {
+        self.node.prop_set_i32("selectedIndex", index);
+    }
+
+    pub fn multiple(&self) -> bool {
+        self.node.prop_get_i32("multiple") != 0
+    }
+
+    /// The `<option>` elements, including those inside `<optgroup>`s.
+    pub fn options(&self) -> Vec<HtmlNode<'a>> {
+        let mut start: i32 = 0;
+        let start_ptr: *mut i32 = &mut start;
+        let start_vptr = start_ptr as *const libc::c_void;
+        let count = js! { (self.node.id, start_vptr) b"\
+            var options = WEBPLATFORM.rs_refs[$0].options;\
+            if (options.length == 0) {\
+                return 0;\
+            }\
+            setValue($1, WEBPLATFORM.rs_refs.length, 'i32');\
+            Array.prototype.push.apply(WEBPLATFORM.rs_refs, options);\
+            return options.length;\
+        \0" };
+        (start..(start+count)).map(|id| HtmlNode{ id: id, doc: self.node.doc }).collect()
+    }
+
+    /// The values of all selected options, for `<select multiple>`.
+    pub fn selected_values(&self) -> Vec<String> {
+        self.options().iter()
+            .filter(|o| o.prop_get_i32("selected") != 0)
+            .map(|o| o.prop_get_str("value"))
+            .collect()
+    }
+}
+
+impl<'a> TextAreaElement<'a> {
+    pub fn into_node(self) -> HtmlNode<'a> {
+        self.node
+    }
+
+    pub fn value(&self) -> String {
+        self.node.prop_get_str("value")
+    }
+
+    pub fn value_set(&self, v: &str) {
+        self.node.prop_set_str("value", v);
+    }
+
+    pub fn selection_range(&self) -> Option<(i32, i32)> {
+        selection_range(self.node.id)
+    }
+
+    pub fn selection_range_set(&self, start: i32, end: i32) {
+        selection_range_set(self.node.id, start, end);
+    }
+
+    pub fn select(&self) {
+        js! { (self.node.id) b"\
+            WEBPLATFORM.rs_refs[$0].select();\
+        \0" };
+    }
+}
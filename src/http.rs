@@ -0,0 +1,526 @@
+use libc;
+use std::ffi::{CString, CStr};
+use std::str;
+use std::borrow::ToOwned;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use {Interop, Document, listen, exception_message, json_check};
+
+/// Which requests carry cookies and HTTP auth. XMLHttpRequest always sends
+/// them to the same origin, so there is no mode that omits them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Credentials {
+    SameOrigin,
+    /// Also send them cross-origin.
+    Include,
+}
+
+#[derive(Debug, Clone)]
+enum Body {
+    Empty,
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+/// An HTTP request, sent with XMLHttpRequest.
+#[derive(Debug, Clone)]
+pub struct Request {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Body,
+    timeout: i32,
+    credentials: Credentials,
+}
+
+/// The response to a `Request`, backed by the XMLHttpRequest that fetched
+/// it or, for a `LocalServer`, held in memory.
+pub struct Response {
+    inner: ResponseInner,
+}
+
+enum ResponseInner {
+    Xhr(libc::c_int),
+    Local {
+        status: i32,
+        status_text: String,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    },
+}
+
+struct FutureState {
+    result: Option<Result<Response, String>>,
+    waker: Option<Waker>,
+}
+
+/// The response to `Request::send_future`.
+pub struct ResponseFuture {
+    state: Rc<RefCell<FutureState>>,
+}
+
+impl Future for ResponseFuture {
+    type Output = Result<Response, String>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<Response, String>> {
+        let mut state = self.state.borrow_mut();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Request {
+    pub fn new(method: &str, url: &str) -> Request {
+        Request {
+            method: method.to_owned(),
+            url: url.to_owned(),
+            headers: Vec::new(),
+            body: Body::Empty,
+            timeout: 0,
+            credentials: Credentials::SameOrigin,
+        }
+    }
+
+    pub fn get(url: &str) -> Request {
+        Request::new("GET", url)
+    }
+
+    pub fn post(url: &str) -> Request {
+        Request::new("POST", url)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Request {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn body_str(mut self, body: &str) -> Request {
+        self.body = Body::Text(body.to_owned());
+        self
+    }
+
+    pub fn body_bytes(mut self, body: &[u8]) -> Request {
+        self.body = Body::Bytes(body.to_vec());
+        self
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    pub fn body(&self) -> &[u8] {
+        match self.body {
+            Body::Empty => &[],
+            Body::Text(ref s) => s.as_bytes(),
+            Body::Bytes(ref b) => b,
+        }
+    }
+
+    /// Timeout in milliseconds; 0 (the default) waits indefinitely.
+    pub fn timeout(mut self, ms: i32) -> Request {
+        self.timeout = ms;
+        self
+    }
+
+    pub fn credentials(mut self, credentials: Credentials) -> Request {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Sends the request and calls `f` once with the response, or with an
+    /// error if the method, URL or a header is invalid or the request fails.
+    /// `f` is never called before `send` returns.
+    pub fn send<'a, F>(self, doc: &Document<'a>, f: F)
+        where F: FnOnce(Result<Response, String>) + 'a
+    {
+        let id = js! { b"\
+            var xhr = new XMLHttpRequest();\
+            xhr.responseType = 'arraybuffer';\
+            return WEBPLATFORM.rs_refs.push(xhr) - 1;\
+        \0" };
+
+        let mut err = js! { (id, &self.method[..], &self.url[..], self.timeout,
+            (self.credentials == Credentials::Include) as i32)
+            b"\
+            var xhr = WEBPLATFORM.rs_refs[$0];\
+            try {\
+                xhr.open(UTF8ToString($1), UTF8ToString($2), true);\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            xhr.timeout = $3;\
+            xhr.withCredentials = !!$4;\
+            return 0;\
+        \0" };
+        for &(ref name, ref value) in &self.headers {
+            if err < 0 {
+                break;
+            }
+            err = js! { (id, &name[..], &value[..]) b"\
+                try {\
+                    WEBPLATFORM.rs_refs[$0].setRequestHeader(UTF8ToString($1), UTF8ToString($2));\
+                } catch (e) {\
+                    return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+                }\
+                return 0;\
+            \0" };
+        }
+        if err < 0 {
+            // Report the exception asynchronously, like any other failure.
+            let mut f = Some(f);
+            listen(doc, id, "rs-exception", false, move |_| {
+                if let Some(f) = f.take() {
+                    f(Err(exception_message(err)));
+                }
+            });
+            js! { (id) b"\
+                var xhr = WEBPLATFORM.rs_refs[$0];\
+                setTimeout(function () {\
+                    xhr.dispatchEvent(new Event('rs-exception'));\
+                }, 0);\
+            \0" };
+            return;
+        }
+
+        let done = Rc::new(RefCell::new(Some(f)));
+        let done_error = done.clone();
+        let done_timeout = done.clone();
+        let done_abort = done.clone();
+        listen(doc, id, "load", false, move |_| {
+            if let Some(f) = done.borrow_mut().take() {
+                f(Ok(Response { inner: ResponseInner::Xhr(id) }));
+            }
+        });
+        listen(doc, id, "error", false, move |_| {
+            if let Some(f) = done_error.borrow_mut().take() {
+                f(Err("network error".to_owned()));
+            }
+        });
+        listen(doc, id, "timeout", false, move |_| {
+            if let Some(f) = done_timeout.borrow_mut().take() {
+                f(Err("timed out".to_owned()));
+            }
+        });
+        listen(doc, id, "abort", false, move |_| {
+            if let Some(f) = done_abort.borrow_mut().take() {
+                f(Err("aborted".to_owned()));
+            }
+        });
+
+        match self.body {
+            Body::Empty => {
+                js! { (id) b"\
+                    WEBPLATFORM.rs_refs[$0].send();\
+                \0" };
+            }
+            Body::Text(ref s) => {
+                js! { (id, &s[..]) b"\
+                    WEBPLATFORM.rs_refs[$0].send(UTF8ToString($1));\
+                \0" };
+            }
+            Body::Bytes(ref b) => {
+                js! { (id, b.as_ptr() as *const libc::c_void, b.len() as i32) b"\
+                    WEBPLATFORM.rs_refs[$0].send(HEAPU8.slice($1, $1 + $2));\
+                \0" };
+            }
+        }
+    }
+
+    /// Like `send`, but resolves a future instead of calling back.
+    pub fn send_future(self, doc: &Document) -> ResponseFuture {
+        let state = Rc::new(RefCell::new(FutureState {
+            result: None,
+            waker: None,
+        }));
+        let done = state.clone();
+        self.send(doc, move |result| {
+            let waker = {
+                let mut state = done.borrow_mut();
+                state.result = Some(result);
+                state.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        });
+        ResponseFuture {
+            state: state,
+        }
+    }
+}
+
+impl Response {
+    /// A response held in memory, for `LocalServer` handlers.
+    pub fn new(status: i32, status_text: &str, body: &[u8]) -> Response {
+        Response {
+            inner: ResponseInner::Local {
+                status: status,
+                status_text: status_text.to_owned(),
+                headers: Vec::new(),
+                body: body.to_vec(),
+            },
+        }
+    }
+
+    /// Adds a header to a response made with `Response::new`.
+    pub fn with_header(mut self, name: &str, value: &str) -> Response {
+        if let ResponseInner::Local { ref mut headers, .. } = self.inner {
+            headers.push((name.to_owned(), value.to_owned()));
+        }
+        self
+    }
+
+    pub fn status(&self) -> i32 {
+        let id = match self.inner {
+            ResponseInner::Xhr(id) => id,
+            ResponseInner::Local { status, .. } => return status,
+        };
+        js! { (id) b"\
+            return WEBPLATFORM.rs_refs[$0].status;\
+        \0" }
+    }
+
+    pub fn status_text(&self) -> String {
+        let id = match self.inner {
+            ResponseInner::Xhr(id) => id,
+            ResponseInner::Local { ref status_text, .. } => return status_text.clone(),
+        };
+        let a = js! { (id) b"\
+            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].statusText), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    /// Header names are matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<String> {
+        let id = match self.inner {
+            ResponseInner::Xhr(id) => id,
+            ResponseInner::Local { ref headers, .. } => {
+                return headers.iter()
+                    .find(|&&(ref n, _)| n.eq_ignore_ascii_case(name))
+                    .map(|&(_, ref v)| v.clone());
+            }
+        };
+        let a = js! { (id, name) b"\
+            var str = WEBPLATFORM.rs_refs[$0].getResponseHeader(UTF8ToString($1));\
+            if (str == null) return -1;\
+            return allocate(intArrayFromString(str), 'i8', ALLOC_STACK);\
+        \0" };
+        if a == -1 {
+            None
+        } else {
+            Some(unsafe {
+                str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+            })
+        }
+    }
+
+    pub fn headers(&self) -> Vec<(String, String)> {
+        let id = match self.inner {
+            ResponseInner::Xhr(id) => id,
+            ResponseInner::Local { ref headers, .. } => return headers.clone(),
+        };
+        let a = js! { (id) b"\
+            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].getAllResponseHeaders()), 'i8', ALLOC_STACK);\
+        \0" };
+        let raw = unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        };
+        raw.split("\r\n").filter_map(|line| {
+            line.find(':').map(|i| (line[..i].trim().to_owned(), line[i + 1..].trim().to_owned()))
+        }).collect()
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        let id = match self.inner {
+            ResponseInner::Xhr(id) => id,
+            ResponseInner::Local { ref body, .. } => return body.clone(),
+        };
+        let len = js! { (id) b"\
+            var buf = WEBPLATFORM.rs_refs[$0].response;\
+            return buf ? buf.byteLength : 0;\
+        \0" };
+        let mut buf = vec![0u8; len as usize];
+        if len > 0 {
+            js! { (id, buf.as_mut_ptr() as *const libc::c_void) b"\
+                HEAPU8.set(new Uint8Array(WEBPLATFORM.rs_refs[$0].response), $1);\
+            \0" };
+        }
+        buf
+    }
+
+    /// The body decoded as UTF-8, with invalid sequences replaced.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.bytes()).into_owned()
+    }
+
+    /// The body as JSON text, to hand to whichever parser the caller uses,
+    /// or the parse error if it isn't JSON.
+    pub fn json(&self) -> Result<String, String> {
+        let id = match self.inner {
+            ResponseInner::Xhr(id) => id,
+            ResponseInner::Local { .. } => {
+                let text = self.text();
+                return json_check(&text).map(|()| text);
+            }
+        };
+        let err = js! { (id) b"\
+            var buf = WEBPLATFORM.rs_refs[$0].response;\
+            try {\
+                JSON.parse(new TextDecoder('utf-8').decode(buf ? new Uint8Array(buf) : new Uint8Array(0)));\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return 0;\
+        \0" };
+        if err < 0 {
+            Err(exception_message(err))
+        } else {
+            Ok(self.text())
+        }
+    }
+}
+
+/// Something a `Request` can be sent through: the browser, via `Document`,
+/// or a `LocalServer` in tests. In both, the callback runs after `send`
+/// returns.
+pub trait HttpClient<'a> {
+    fn send<F>(&self, request: Request, f: F)
+        where F: FnOnce(Result<Response, String>) + 'a;
+}
+
+impl<'a> HttpClient<'a> for Document<'a> {
+    fn send<F>(&self, request: Request, f: F)
+        where F: FnOnce(Result<Response, String>) + 'a
+    {
+        request.send(self, f);
+    }
+}
+
+type LocalHandler<'a> = Box<dyn Fn(&Request) -> Response + 'a>;
+type LocalCallback<'a> = Box<dyn FnOnce(Result<Response, String>) + 'a>;
+
+/// An in-memory HTTP stand-in for tests that run outside the browser.
+/// Requests are queued by `send` and answered by `flush`, from the handler
+/// registered for their method and path, or with a 404.
+pub struct LocalServer<'a> {
+    routes: RefCell<Vec<(String, String, LocalHandler<'a>)>>,
+    pending: RefCell<Vec<(Request, LocalCallback<'a>)>>,
+}
+
+impl<'a> LocalServer<'a> {
+    pub fn new() -> LocalServer<'a> {
+        LocalServer {
+            routes: RefCell::new(Vec::new()),
+            pending: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Answers `method` requests for `path`, which is matched against the
+    /// request URL without its origin and query.
+    pub fn route<F: Fn(&Request) -> Response + 'a>(&self, method: &str, path: &str, f: F) {
+        self.routes.borrow_mut().push((method.to_owned(), path.to_owned(), Box::new(f)));
+    }
+
+    /// Answers every queued request, returning how many there were.
+    /// Requests sent from the callbacks wait for the next `flush`.
+    pub fn flush(&self) -> usize {
+        let pending = self.pending.replace(Vec::new());
+        let count = pending.len();
+        for (request, f) in pending {
+            let response = {
+                let path = local_path(request.url());
+                let routes = self.routes.borrow();
+                routes.iter()
+                    .find(|&&(ref method, ref p, _)| method.eq_ignore_ascii_case(request.method()) && p == path)
+                    .map(|&(_, _, ref handler)| handler(&request))
+            };
+            f(Ok(response.unwrap_or_else(|| Response::new(404, "Not Found", b""))));
+        }
+        count
+    }
+}
+
+impl<'a> Default for LocalServer<'a> {
+    fn default() -> LocalServer<'a> {
+        LocalServer::new()
+    }
+}
+
+impl<'a> HttpClient<'a> for LocalServer<'a> {
+    fn send<F>(&self, request: Request, f: F)
+        where F: FnOnce(Result<Response, String>) + 'a
+    {
+        self.pending.borrow_mut().push((request, Box::new(f)));
+    }
+}
+
+// `https://host/a/b?q` -> `/a/b`.
+fn local_path(url: &str) -> &str {
+    let url = match url.find("://") {
+        Some(i) => {
+            let rest = &url[i + 3..];
+            rest.find('/').map(|j| &rest[j..]).unwrap_or("/")
+        }
+        None => url,
+    };
+    let end = url.find(|c| c == '?' || c == '#').unwrap_or(url.len());
+    &url[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::{local_path, HttpClient, LocalServer, Request, Response};
+
+    #[test]
+    fn local_path_strips_origin_and_query() {
+        assert_eq!(local_path("https://example.com/api/users?page=2"), "/api/users");
+        assert_eq!(local_path("https://example.com"), "/");
+        assert_eq!(local_path("/api#top"), "/api");
+    }
+
+    #[test]
+    fn local_server_answers_on_flush() {
+        let server = LocalServer::new();
+        server.route("GET", "/api/user", |req| {
+            Response::new(200, "OK", format!("{{\"q\": \"{}\"}}", req.url()).as_bytes())
+                .with_header("Content-Type", "application/json")
+        });
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let s = seen.clone();
+        server.send(Request::get("/api/user?id=1"), move |res| {
+            let res = res.unwrap();
+            s.borrow_mut().push((res.status(), res.header("content-type"), res.json()));
+        });
+        let s = seen.clone();
+        server.send(Request::post("/missing"), move |res| {
+            s.borrow_mut().push((res.unwrap().status(), None, Err(String::new())));
+        });
+        assert!(seen.borrow().is_empty());
+        assert_eq!(server.flush(), 2);
+        let seen = seen.borrow();
+        assert_eq!(seen[0], (200, Some("application/json".to_owned()), Ok("{\"q\": \"/api/user?id=1\"}".to_owned())));
+        assert_eq!(seen[1].0, 404);
+    }
+}
@@ -0,0 +1,1008 @@
+use libc;
+use std::ffi::{CString, CStr};
+use std::str;
+use std::borrow::ToOwned;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::marker::PhantomData;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, Bound};
+
+use {Interop, Document, listen, string_list, exception_message, json_check};
+
+pub struct Database<'a> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+}
+
+pub struct Transaction<'a> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+}
+
+pub struct ObjectStore<'a> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+}
+
+pub struct Index<'a> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+}
+
+pub struct Cursor<'a> {
+    id: libc::c_int,
+    _doc: PhantomData<&'a Document<'a>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionMode {
+    ReadOnly,
+    ReadWrite,
+}
+
+impl TransactionMode {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            TransactionMode::ReadOnly => "readonly",
+            TransactionMode::ReadWrite => "readwrite",
+        }
+    }
+}
+
+fn request_error(req: libc::c_int) -> String {
+    let a = js! { (req) b"\
+        var err = WEBPLATFORM.rs_refs[$0].error;\
+        var str = err ? err.name + ': ' + err.message : 'unknown error';\
+        return allocate(intArrayFromString(str), 'i8', ALLOC_STACK);\
+    \0" };
+    unsafe {
+        str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+    }
+}
+
+// Turns the `-2 - id` an exception-catching snippet returns into its message.
+fn checked(id: libc::c_int) -> Result<libc::c_int, String> {
+    if id < 0 {
+        Err(exception_message(id))
+    } else {
+        Ok(id)
+    }
+}
+
+// Wires the `success` and `error` events of an IDBRequest to a single callback,
+// which is handed the request id so the caller can read `request.result`.
+fn request<'a, F>(doc: *const Document<'a>, req: libc::c_int, f: F)
+    where F: FnOnce(Result<libc::c_int, String>) + 'a
+{
+    if req < 0 {
+        f(Err(exception_message(req)));
+        return;
+    }
+    let done = Rc::new(RefCell::new(Some(f)));
+    let done_err = done.clone();
+    listen(doc, req, "success", false, move |_| {
+        if let Some(f) = done.borrow_mut().take() {
+            f(Ok(req));
+        }
+    });
+    listen(doc, req, "error", false, move |_| {
+        if let Some(f) = done_err.borrow_mut().take() {
+            f(Err(request_error(req)));
+        }
+    });
+}
+
+fn result_json(req: libc::c_int) -> Option<String> {
+    let a = js! { (req) b"\
+        var value = WEBPLATFORM.rs_refs[$0].result;\
+        if (value === undefined) return -1;\
+        return allocate(intArrayFromString(JSON.stringify(value)), 'i8', ALLOC_STACK);\
+    \0" };
+    if a == -1 {
+        None
+    } else {
+        Some(unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        })
+    }
+}
+
+fn result_key(req: libc::c_int) -> String {
+    let a = js! { (req) b"\
+        return allocate(intArrayFromString(JSON.stringify(WEBPLATFORM.rs_refs[$0].result)), 'i8', ALLOC_STACK);\
+    \0" };
+    unsafe {
+        str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+    }
+}
+
+fn source_get<'a, F>(doc: *const Document<'a>, id: libc::c_int, key: &str, f: F)
+    where F: FnOnce(Result<Option<String>, String>) + 'a
+{
+    let req = js! { (id, key) b"\
+        try {\
+            var req = WEBPLATFORM.rs_refs[$0].get(JSON.parse(UTF8ToString($1)));\
+        } catch (e) {\
+            return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+        }\
+        return WEBPLATFORM.rs_refs.push(req) - 1;\
+    \0" };
+    request(doc, req, move |res| f(res.map(result_json)));
+}
+
+fn source_count<'a, F>(doc: *const Document<'a>, id: libc::c_int, f: F)
+    where F: FnOnce(Result<i32, String>) + 'a
+{
+    let req = js! { (id) b"\
+        try {\
+            var req = WEBPLATFORM.rs_refs[$0].count();\
+        } catch (e) {\
+            return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+        }\
+        return WEBPLATFORM.rs_refs.push(req) - 1;\
+    \0" };
+    request(doc, req, move |res| f(res.map(|req| js! { (req) b"\
+        return WEBPLATFORM.rs_refs[$0].result;\
+    \0" })));
+}
+
+fn source_open_cursor<'a, F>(doc: *const Document<'a>, id: libc::c_int, mut f: F)
+    where F: FnMut(Result<Option<Cursor<'a>>, String>) + 'a
+{
+    let req = js! { (id) b"\
+        try {\
+            var req = WEBPLATFORM.rs_refs[$0].openCursor();\
+        } catch (e) {\
+            return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+        }\
+        return WEBPLATFORM.rs_refs.push(req) - 1;\
+    \0" };
+    if req < 0 {
+        return f(Err(exception_message(req)));
+    }
+    // Unlike other requests, a cursor request fires `success` once per record.
+    let f = Rc::new(RefCell::new(f));
+    let f_err = f.clone();
+    listen(doc, req, "success", false, move |_| {
+        let cursor = js! { (req) b"\
+            var value = WEBPLATFORM.rs_refs[$0].result;\
+            if (!value) {\
+                return -1;\
+            }\
+            return WEBPLATFORM.rs_refs.push(value) - 1;\
+        \0" };
+        (&mut *f.borrow_mut())(Ok(if cursor < 0 {
+            None
+        } else {
+            Some(Cursor {
+                id: cursor,
+                _doc: PhantomData,
+            })
+        }));
+    });
+    listen(doc, req, "error", false, move |_| {
+        (&mut *f_err.borrow_mut())(Err(request_error(req)));
+    });
+}
+
+impl<'a> Document<'a> {
+    /// Opens the named database, calling `upgrade` first if the stored
+    /// version is older than `version` (the old version is passed along,
+    /// 0 for a brand new database), then `done` once the database is ready.
+    pub fn indexed_db_open<U, F>(&self, name: &str, version: i32, upgrade: U, done: F)
+        where U: FnOnce(Database<'a>, Transaction<'a>, i32) + 'a,
+              F: FnOnce(Result<Database<'a>, String>) + 'a
+    {
+        let doc: *const Document<'a> = &*self;
+        let req = js! { (name, version) b"\
+            try {\
+                var req = window.indexedDB.open(UTF8ToString($0), $1);\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return WEBPLATFORM.rs_refs.push(req) - 1;\
+        \0" };
+
+        if req < 0 {
+            return done(Err(exception_message(req)));
+        }
+
+        let mut upgrade = Some(upgrade);
+        listen(doc, req, "upgradeneeded", false, move |_| {
+            let old_version = js! { b"\
+                return WEBPLATFORM.event.oldVersion;\
+            \0" };
+            let db = js! { (req) b"\
+                return WEBPLATFORM.rs_refs.push(WEBPLATFORM.rs_refs[$0].result) - 1;\
+            \0" };
+            let tx = js! { (req) b"\
+                return WEBPLATFORM.rs_refs.push(WEBPLATFORM.rs_refs[$0].transaction) - 1;\
+            \0" };
+            if let Some(upgrade) = upgrade.take() {
+                upgrade(Database { id: db, doc: doc }, Transaction { id: tx, doc: doc }, old_version);
+            }
+        });
+
+        request(doc, req, move |res| done(res.map(|req| {
+            let db = js! { (req) b"\
+                return WEBPLATFORM.rs_refs.push(WEBPLATFORM.rs_refs[$0].result) - 1;\
+            \0" };
+            Database {
+                id: db,
+                doc: doc,
+            }
+        })));
+    }
+
+    pub fn indexed_db_delete<F>(&self, name: &str, done: F)
+        where F: FnOnce(Result<(), String>) + 'a
+    {
+        let req = js! { (name) b"\
+            try {\
+                var req = window.indexedDB.deleteDatabase(UTF8ToString($0));\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return WEBPLATFORM.rs_refs.push(req) - 1;\
+        \0" };
+        request(&*self, req, move |res| done(res.map(|_| ())));
+    }
+}
+
+impl<'a> Database<'a> {
+    pub fn name(&self) -> String {
+        let a = js! { (self.id) b"\
+            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].name), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    pub fn version(&self) -> i32 {
+        js! { (self.id) b"\
+            return WEBPLATFORM.rs_refs[$0].version;\
+        \0" }
+    }
+
+    pub fn object_store_names(&self) -> Vec<String> {
+        let list = js! { (self.id) b"\
+            return WEBPLATFORM.rs_refs.push(WEBPLATFORM.rs_refs[$0].objectStoreNames) - 1;\
+        \0" };
+        string_list(list)
+    }
+
+    /// Only valid from within the `upgrade` callback of `indexed_db_open`.
+    pub fn create_object_store(&self, name: &str, key_path: Option<&str>, auto_increment: bool) -> Result<ObjectStore<'a>, String> {
+        let id = js! { (self.id, name, key_path.unwrap_or(""), key_path.is_some() as i32, auto_increment as i32) b"\
+            var options = { autoIncrement: !!$4 };\
+            if ($3) {\
+                options.keyPath = UTF8ToString($2);\
+            }\
+            try {\
+                var store = WEBPLATFORM.rs_refs[$0].createObjectStore(UTF8ToString($1), options);\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return WEBPLATFORM.rs_refs.push(store) - 1;\
+        \0" };
+        checked(id).map(|id| ObjectStore {
+            id: id,
+            doc: self.doc,
+        })
+    }
+
+    /// Only valid from within the `upgrade` callback of `indexed_db_open`.
+    pub fn delete_object_store(&self, name: &str) -> Result<(), String> {
+        let err = js! { (self.id, name) b"\
+            try {\
+                WEBPLATFORM.rs_refs[$0].deleteObjectStore(UTF8ToString($1));\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return 0;\
+        \0" };
+        checked(err).map(|_| ())
+    }
+
+    pub fn transaction(&self, stores: &[&str], mode: TransactionMode) -> Result<Transaction<'a>, String> {
+        let names = js! { b"\
+            return WEBPLATFORM.rs_refs.push([]) - 1;\
+        \0" };
+        for store in stores {
+            js! { (names, *store) b"\
+                WEBPLATFORM.rs_refs[$0].push(UTF8ToString($1));\
+            \0" };
+        }
+        let id = js! { (self.id, names, mode.as_str()) b"\
+            try {\
+                var tx = WEBPLATFORM.rs_refs[$0].transaction(WEBPLATFORM.rs_refs[$1], UTF8ToString($2));\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return WEBPLATFORM.rs_refs.push(tx) - 1;\
+        \0" };
+        checked(id).map(|id| Transaction {
+            id: id,
+            doc: self.doc,
+        })
+    }
+
+    pub fn close(&self) {
+        js! { (self.id) b"\
+            WEBPLATFORM.rs_refs[$0].close();\
+        \0" };
+    }
+}
+
+impl<'a> Transaction<'a> {
+    pub fn object_store(&self, name: &str) -> Result<ObjectStore<'a>, String> {
+        let id = js! { (self.id, name) b"\
+            try {\
+                var store = WEBPLATFORM.rs_refs[$0].objectStore(UTF8ToString($1));\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return WEBPLATFORM.rs_refs.push(store) - 1;\
+        \0" };
+        checked(id).map(|id| ObjectStore {
+            id: id,
+            doc: self.doc,
+        })
+    }
+
+    /// Fails if the transaction has already finished.
+    pub fn abort(&self) -> Result<(), String> {
+        let err = js! { (self.id) b"\
+            try {\
+                WEBPLATFORM.rs_refs[$0].abort();\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return 0;\
+        \0" };
+        checked(err).map(|_| ())
+    }
+
+    pub fn on_complete<F: FnOnce() + 'a>(&self, f: F) {
+        let mut f = Some(f);
+        listen(self.doc, self.id, "complete", false, move |_| {
+            if let Some(f) = f.take() {
+                f();
+            }
+        });
+    }
+
+    pub fn on_error<F: FnOnce(String) + 'a>(&self, f: F) {
+        let id = self.id;
+        let mut f = Some(f);
+        listen(self.doc, self.id, "error", false, move |_| {
+            if let Some(f) = f.take() {
+                f(request_error(id));
+            }
+        });
+    }
+}
+
+impl<'a> ObjectStore<'a> {
+    pub fn name(&self) -> String {
+        let a = js! { (self.id) b"\
+            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].name), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    /// Only valid from within the `upgrade` callback of `indexed_db_open`.
+    pub fn create_index(&self, name: &str, key_path: &str, unique: bool) -> Result<Index<'a>, String> {
+        let id = js! { (self.id, name, key_path, unique as i32) b"\
+            try {\
+                var index = WEBPLATFORM.rs_refs[$0].createIndex(UTF8ToString($1), UTF8ToString($2), { unique: !!$3 });\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return WEBPLATFORM.rs_refs.push(index) - 1;\
+        \0" };
+        checked(id).map(|id| Index {
+            id: id,
+            doc: self.doc,
+        })
+    }
+
+    pub fn index(&self, name: &str) -> Result<Index<'a>, String> {
+        let id = js! { (self.id, name) b"\
+            try {\
+                var index = WEBPLATFORM.rs_refs[$0].index(UTF8ToString($1));\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return WEBPLATFORM.rs_refs.push(index) - 1;\
+        \0" };
+        checked(id).map(|id| Index {
+            id: id,
+            doc: self.doc,
+        })
+    }
+
+    /// Looks up `key` (JSON, e.g. `"\"alice\""` or `"1"`), passing the
+    /// stored value as JSON.
+    pub fn get<F>(&self, key: &str, f: F)
+        where F: FnOnce(Result<Option<String>, String>) + 'a
+    {
+        source_get(self.doc, self.id, key, f);
+    }
+
+    /// Stores `value` (JSON), under `key` (JSON) unless the store has a key
+    /// path or generates keys. The callback receives the key the value was
+    /// stored under, as JSON, so it can be passed back to `get` and `delete`.
+    pub fn put<F>(&self, value: &str, key: Option<&str>, f: F)
+        where F: FnOnce(Result<String, String>) + 'a
+    {
+        let req = js! { (self.id, value, key.unwrap_or(""), key.is_some() as i32) b"\
+            var store = WEBPLATFORM.rs_refs[$0];\
+            try {\
+                var value = JSON.parse(UTF8ToString($1));\
+                var req = $3 ? store.put(value, JSON.parse(UTF8ToString($2))) : store.put(value);\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return WEBPLATFORM.rs_refs.push(req) - 1;\
+        \0" };
+        request(self.doc, req, move |res| f(res.map(result_key)));
+    }
+
+    /// Deletes the record under `key` (JSON).
+    pub fn delete<F>(&self, key: &str, f: F)
+        where F: FnOnce(Result<(), String>) + 'a
+    {
+        let req = js! { (self.id, key) b"\
+            try {\
+                var req = WEBPLATFORM.rs_refs[$0].delete(JSON.parse(UTF8ToString($1)));\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return WEBPLATFORM.rs_refs.push(req) - 1;\
+        \0" };
+        request(self.doc, req, move |res| f(res.map(|_| ())));
+    }
+
+    pub fn clear<F>(&self, f: F)
+        where F: FnOnce(Result<(), String>) + 'a
+    {
+        let req = js! { (self.id) b"\
+            try {\
+                var req = WEBPLATFORM.rs_refs[$0].clear();\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return WEBPLATFORM.rs_refs.push(req) - 1;\
+        \0" };
+        request(self.doc, req, move |res| f(res.map(|_| ())));
+    }
+
+    pub fn count<F>(&self, f: F)
+        where F: FnOnce(Result<i32, String>) + 'a
+    {
+        source_count(self.doc, self.id, f);
+    }
+
+    /// Calls `f` once per record, then once more with `None` at the end.
+    /// The cursor only moves on when `Cursor::continue_` is called.
+    pub fn open_cursor<F>(&self, f: F)
+        where F: FnMut(Result<Option<Cursor<'a>>, String>) + 'a
+    {
+        source_open_cursor(self.doc, self.id, f);
+    }
+}
+
+impl<'a> Index<'a> {
+    pub fn name(&self) -> String {
+        let a = js! { (self.id) b"\
+            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].name), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    /// Looks up the first record whose index key is `key` (JSON).
+    pub fn get<F>(&self, key: &str, f: F)
+        where F: FnOnce(Result<Option<String>, String>) + 'a
+    {
+        source_get(self.doc, self.id, key, f);
+    }
+
+    pub fn count<F>(&self, f: F)
+        where F: FnOnce(Result<i32, String>) + 'a
+    {
+        source_count(self.doc, self.id, f);
+    }
+
+    pub fn open_cursor<F>(&self, f: F)
+        where F: FnMut(Result<Option<Cursor<'a>>, String>) + 'a
+    {
+        source_open_cursor(self.doc, self.id, f);
+    }
+}
+
+impl<'a> Cursor<'a> {
+    /// The current key as JSON.
+    pub fn key(&self) -> String {
+        let a = js! { (self.id) b"\
+            return allocate(intArrayFromString(JSON.stringify(WEBPLATFORM.rs_refs[$0].key)), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    /// The current primary key as JSON.
+    pub fn primary_key(&self) -> String {
+        let a = js! { (self.id) b"\
+            return allocate(intArrayFromString(JSON.stringify(WEBPLATFORM.rs_refs[$0].primaryKey)), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    /// The current record as JSON.
+    pub fn value(&self) -> String {
+        let a = js! { (self.id) b"\
+            return allocate(intArrayFromString(JSON.stringify(WEBPLATFORM.rs_refs[$0].value)), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    /// Replaces the current record with `value` (JSON).
+    pub fn update(&self, value: &str) -> Result<(), String> {
+        let err = js! { (self.id, value) b"\
+            try {\
+                WEBPLATFORM.rs_refs[$0].update(JSON.parse(UTF8ToString($1)));\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return 0;\
+        \0" };
+        checked(err).map(|_| ())
+    }
+
+    pub fn delete(&self) -> Result<(), String> {
+        let err = js! { (self.id) b"\
+            try {\
+                WEBPLATFORM.rs_refs[$0].delete();\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return 0;\
+        \0" };
+        checked(err).map(|_| ())
+    }
+
+    /// Fails once the transaction has finished, e.g. if called after
+    /// returning to the event loop.
+    pub fn continue_(&self) -> Result<(), String> {
+        let err = js! { (self.id) b"\
+            try {\
+                WEBPLATFORM.rs_refs[$0]['continue']();\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return 0;\
+        \0" };
+        checked(err).map(|_| ())
+    }
+}
+
+/// The record operations of an object store, shared by `ObjectStore` and
+/// `MemoryStore`, its in-memory stand-in for tests. Keys and values are
+/// JSON text.
+pub trait RecordStore<'a> {
+    type Cursor: RecordCursor;
+
+    fn get<F>(&self, key: &str, f: F)
+        where F: FnOnce(Result<Option<String>, String>) + 'a;
+
+    fn put<F>(&self, value: &str, key: Option<&str>, f: F)
+        where F: FnOnce(Result<String, String>) + 'a;
+
+    fn delete<F>(&self, key: &str, f: F)
+        where F: FnOnce(Result<(), String>) + 'a;
+
+    fn clear<F>(&self, f: F)
+        where F: FnOnce(Result<(), String>) + 'a;
+
+    fn count<F>(&self, f: F)
+        where F: FnOnce(Result<i32, String>) + 'a;
+
+    fn open_cursor<F>(&self, f: F)
+        where F: FnMut(Result<Option<Self::Cursor>, String>) + 'a;
+}
+
+/// A position in a `RecordStore`, as handed to `open_cursor` callbacks.
+pub trait RecordCursor {
+    fn key(&self) -> String;
+    fn primary_key(&self) -> String;
+    fn value(&self) -> String;
+    fn update(&self, value: &str) -> Result<(), String>;
+    fn delete(&self) -> Result<(), String>;
+    fn continue_(&self) -> Result<(), String>;
+}
+
+impl<'a> RecordStore<'a> for ObjectStore<'a> {
+    type Cursor = Cursor<'a>;
+
+    fn get<F>(&self, key: &str, f: F)
+        where F: FnOnce(Result<Option<String>, String>) + 'a
+    {
+        ObjectStore::get(self, key, f);
+    }
+
+    fn put<F>(&self, value: &str, key: Option<&str>, f: F)
+        where F: FnOnce(Result<String, String>) + 'a
+    {
+        ObjectStore::put(self, value, key, f);
+    }
+
+    fn delete<F>(&self, key: &str, f: F)
+        where F: FnOnce(Result<(), String>) + 'a
+    {
+        ObjectStore::delete(self, key, f);
+    }
+
+    fn clear<F>(&self, f: F)
+        where F: FnOnce(Result<(), String>) + 'a
+    {
+        ObjectStore::clear(self, f);
+    }
+
+    fn count<F>(&self, f: F)
+        where F: FnOnce(Result<i32, String>) + 'a
+    {
+        ObjectStore::count(self, f);
+    }
+
+    fn open_cursor<F>(&self, f: F)
+        where F: FnMut(Result<Option<Cursor<'a>>, String>) + 'a
+    {
+        ObjectStore::open_cursor(self, f);
+    }
+}
+
+impl<'a> RecordCursor for Cursor<'a> {
+    fn key(&self) -> String {
+        Cursor::key(self)
+    }
+
+    fn primary_key(&self) -> String {
+        Cursor::primary_key(self)
+    }
+
+    fn value(&self) -> String {
+        Cursor::value(self)
+    }
+
+    fn update(&self, value: &str) -> Result<(), String> {
+        Cursor::update(self, value)
+    }
+
+    fn delete(&self) -> Result<(), String> {
+        Cursor::delete(self)
+    }
+
+    fn continue_(&self) -> Result<(), String> {
+        Cursor::continue_(self)
+    }
+}
+
+// Orders keys the way IndexedDB does for the kinds `MemoryStore` stores:
+// numbers first, numerically, then strings and arrays by their JSON text.
+#[derive(Debug, Clone, PartialEq)]
+enum MemoryKey {
+    Number(f64),
+    Other(String),
+}
+
+impl MemoryKey {
+    fn parse(key: &str) -> Result<MemoryKey, String> {
+        let key = key.trim();
+        if key.starts_with('"') || key.starts_with('[') {
+            return json_check(key).map(|()| MemoryKey::Other(key.to_owned()));
+        }
+        // `parse` also takes `NaN` and `inf`, which aren't JSON and which
+        // IndexedDB rejects as keys; NaN would also break the ordering.
+        match key.parse::<f64>() {
+            Ok(n) if n.is_finite() && json_check(key).is_ok() => Ok(MemoryKey::Number(n)),
+            _ => Err(format!("DataError: {} is not a valid key", key)),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        match *self {
+            MemoryKey::Number(n) => n.to_string(),
+            MemoryKey::Other(ref s) => s.clone(),
+        }
+    }
+}
+
+impl Eq for MemoryKey {}
+
+impl PartialOrd for MemoryKey {
+    fn partial_cmp(&self, other: &MemoryKey) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MemoryKey {
+    fn cmp(&self, other: &MemoryKey) -> Ordering {
+        match (self, other) {
+            // Keys are finite, so `partial_cmp` always has an answer.
+            (&MemoryKey::Number(a), &MemoryKey::Number(b)) => a.partial_cmp(&b).unwrap(),
+            (&MemoryKey::Number(_), &MemoryKey::Other(_)) => Ordering::Less,
+            (&MemoryKey::Other(_), &MemoryKey::Number(_)) => Ordering::Greater,
+            (&MemoryKey::Other(ref a), &MemoryKey::Other(ref b)) => a.cmp(b),
+        }
+    }
+}
+
+struct MemoryState {
+    records: BTreeMap<MemoryKey, String>,
+    auto_increment: bool,
+    next_key: f64,
+}
+
+/// An in-memory `RecordStore` for tests that run outside the browser.
+/// Clones share the same records. Unlike `ObjectStore`, callbacks run
+/// before the method returns.
+#[derive(Clone)]
+pub struct MemoryStore {
+    state: Rc<RefCell<MemoryState>>,
+}
+
+impl MemoryStore {
+    /// With `auto_increment`, `put` without a key generates 1, 2, 3, ...
+    pub fn new(auto_increment: bool) -> MemoryStore {
+        MemoryStore {
+            state: Rc::new(RefCell::new(MemoryState {
+                records: BTreeMap::new(),
+                auto_increment: auto_increment,
+                next_key: 1.0,
+            })),
+        }
+    }
+}
+
+impl<'a> RecordStore<'a> for MemoryStore {
+    type Cursor = MemoryCursor;
+
+    fn get<F>(&self, key: &str, f: F)
+        where F: FnOnce(Result<Option<String>, String>) + 'a
+    {
+        let found = MemoryKey::parse(key).map(|key| self.state.borrow().records.get(&key).cloned());
+        f(found);
+    }
+
+    fn put<F>(&self, value: &str, key: Option<&str>, f: F)
+        where F: FnOnce(Result<String, String>) + 'a
+    {
+        if let Err(e) = json_check(value) {
+            return f(Err(format!("SyntaxError: {}", e)));
+        }
+        let stored = {
+            let mut state = self.state.borrow_mut();
+            let key = match key {
+                Some(key) => MemoryKey::parse(key),
+                None if state.auto_increment => Ok(MemoryKey::Number(state.next_key)),
+                None => Err("DataError: no key given and the store does not generate keys".to_owned()),
+            };
+            key.map(|key| {
+                if let MemoryKey::Number(n) = key {
+                    if state.auto_increment && n >= state.next_key {
+                        state.next_key = n.floor() + 1.0;
+                    }
+                }
+                let json = key.to_json();
+                state.records.insert(key, value.to_owned());
+                json
+            })
+        };
+        f(stored);
+    }
+
+    fn delete<F>(&self, key: &str, f: F)
+        where F: FnOnce(Result<(), String>) + 'a
+    {
+        let deleted = MemoryKey::parse(key).map(|key| {
+            self.state.borrow_mut().records.remove(&key);
+        });
+        f(deleted);
+    }
+
+    fn clear<F>(&self, f: F)
+        where F: FnOnce(Result<(), String>) + 'a
+    {
+        self.state.borrow_mut().records.clear();
+        f(Ok(()));
+    }
+
+    fn count<F>(&self, f: F)
+        where F: FnOnce(Result<i32, String>) + 'a
+    {
+        let count = self.state.borrow().records.len() as i32;
+        f(Ok(count));
+    }
+
+    /// Calls `f` once per record while it calls `continue_`, then once
+    /// more with `None` at the end.
+    fn open_cursor<F>(&self, mut f: F)
+        where F: FnMut(Result<Option<MemoryCursor>, String>) + 'a
+    {
+        let mut after: Option<MemoryKey> = None;
+        loop {
+            let next = {
+                let state = self.state.borrow();
+                let mut range = match after {
+                    Some(ref key) => state.records.range((Bound::Excluded(key.clone()), Bound::Unbounded)),
+                    None => state.records.range::<MemoryKey, _>(..),
+                };
+                range.next().map(|(k, v)| (k.clone(), v.clone()))
+            };
+            let (key, value) = match next {
+                Some(record) => record,
+                None => return f(Ok(None)),
+            };
+            let cursor = MemoryCursor {
+                state: self.state.clone(),
+                key: key.clone(),
+                value: value,
+                active: Rc::new(Cell::new(true)),
+                advance: Rc::new(Cell::new(false)),
+            };
+            let active = cursor.active.clone();
+            let advance = cursor.advance.clone();
+            f(Ok(Some(cursor)));
+            active.set(false);
+            if !advance.get() {
+                return;
+            }
+            after = Some(key);
+        }
+    }
+}
+
+/// The cursor of a `MemoryStore`. Like a `Cursor`, it can only be used
+/// from within the `open_cursor` callback it was passed to.
+pub struct MemoryCursor {
+    state: Rc<RefCell<MemoryState>>,
+    key: MemoryKey,
+    value: String,
+    active: Rc<Cell<bool>>,
+    advance: Rc<Cell<bool>>,
+}
+
+impl MemoryCursor {
+    fn check_active(&self) -> Result<(), String> {
+        if self.active.get() {
+            Ok(())
+        } else {
+            Err("TransactionInactiveError: the cursor's callback has returned".to_owned())
+        }
+    }
+}
+
+impl RecordCursor for MemoryCursor {
+    fn key(&self) -> String {
+        self.key.to_json()
+    }
+
+    fn primary_key(&self) -> String {
+        self.key.to_json()
+    }
+
+    fn value(&self) -> String {
+        self.value.clone()
+    }
+
+    fn update(&self, value: &str) -> Result<(), String> {
+        self.check_active()?;
+        json_check(value).map_err(|e| format!("SyntaxError: {}", e))?;
+        self.state.borrow_mut().records.insert(self.key.clone(), value.to_owned());
+        Ok(())
+    }
+
+    fn delete(&self) -> Result<(), String> {
+        self.check_active()?;
+        self.state.borrow_mut().records.remove(&self.key);
+        Ok(())
+    }
+
+    fn continue_(&self) -> Result<(), String> {
+        self.check_active()?;
+        if self.advance.get() {
+            return Err("InvalidStateError: the cursor is already advancing".to_owned());
+        }
+        self.advance.set(true);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::{MemoryStore, RecordStore, RecordCursor};
+
+    fn keys(store: &MemoryStore) -> Vec<String> {
+        let keys = Rc::new(RefCell::new(Vec::new()));
+        let k = keys.clone();
+        store.open_cursor(move |cursor| {
+            if let Some(cursor) = cursor.unwrap() {
+                k.borrow_mut().push(cursor.key());
+                cursor.continue_().unwrap();
+            }
+        });
+        let keys = keys.borrow().clone();
+        keys
+    }
+
+    #[test]
+    fn memory_store_generates_keys_that_round_trip() {
+        let store = MemoryStore::new(true);
+        let key = Rc::new(RefCell::new(String::new()));
+        let k = key.clone();
+        store.put("{\"a\":1}", None, move |res| *k.borrow_mut() = res.unwrap());
+        let key = key.borrow().clone();
+        assert_eq!(key, "1");
+        store.get(&key, |res| assert_eq!(res.unwrap(), Some("{\"a\":1}".to_owned())));
+        store.delete(&key, |res| res.unwrap());
+        store.count(|res| assert_eq!(res.unwrap(), 0));
+    }
+
+    #[test]
+    fn memory_store_orders_numbers_before_strings() {
+        let store = MemoryStore::new(false);
+        store.put("1", Some("\"a\""), |res| { res.unwrap(); });
+        store.put("2", Some("10"), |res| { res.unwrap(); });
+        store.put("3", Some("2"), |res| { res.unwrap(); });
+        assert_eq!(keys(&store), vec!["2", "10", "\"a\""]);
+    }
+
+    #[test]
+    fn memory_store_rejects_invalid_keys_and_values() {
+        let store = MemoryStore::new(false);
+        store.put("1", None, |res| assert!(res.is_err()));
+        for key in &["NaN", "inf", "-inf", "1e999", "abc", "{}", "true"] {
+            store.put("1", Some(key), |res| assert!(res.unwrap_err().starts_with("DataError"), "{}", key));
+        }
+        store.put("{", Some("1"), |res| assert!(res.is_err()));
+        store.count(|res| assert_eq!(res.unwrap(), 0));
+    }
+
+    #[test]
+    fn memory_cursor_updates_deletes_and_stops() {
+        let store = MemoryStore::new(true);
+        for value in &["1", "2", "3"] {
+            store.put(value, None, |res| { res.unwrap(); });
+        }
+        let saved = Rc::new(RefCell::new(None));
+        let s = saved.clone();
+        store.open_cursor(move |cursor| {
+            let cursor = cursor.unwrap().unwrap();
+            if cursor.key() == "1" {
+                cursor.update("10").unwrap();
+                cursor.continue_().unwrap();
+            } else {
+                cursor.delete().unwrap();
+                *s.borrow_mut() = Some(cursor);
+            }
+        });
+        // The callback stopped at key 2 without continuing; 3 is untouched.
+        assert_eq!(keys(&store), vec!["1", "3"]);
+        store.get("1", |res| assert_eq!(res.unwrap(), Some("10".to_owned())));
+        assert!(saved.borrow().as_ref().unwrap().continue_().is_err());
+    }
+}
@@ -1,6 +1,14 @@
 #![allow(unused_unsafe)]
 
 extern crate libc;
+#[cfg(feature = "log")]
+extern crate log;
+#[cfg(feature = "futures-core")]
+extern crate futures_core;
+#[cfg(feature = "serde-form")]
+extern crate serde;
+#[cfg(feature = "serde-form")]
+extern crate serde_urlencoded;
 
 use std::ffi::{CString, CStr};
 use std::{mem, fmt};
@@ -10,8 +18,7 @@ use std::ops::Deref;
 use std::cell::RefCell;
 use std::clone::Clone;
 use std::rc::Rc;
-use std::collections::HashSet;
-use std::char;
+use std::collections::{HashMap, HashSet};
 use std::iter::IntoIterator;
 
 mod webplatform {
@@ -69,6 +76,31 @@ extern "C" {
     pub fn emscripten_set_main_loop(m: extern fn(), fps: libc::c_int, infinite: libc::c_int);
 }
 
+mod indexed_db;
+mod http;
+mod websocket;
+mod event_source;
+mod css;
+mod form;
+mod router;
+mod cookie;
+mod dialog;
+mod panic_hook;
+pub mod console;
+
+pub use indexed_db::{Database, ObjectStore, Index, Transaction, TransactionMode, Cursor, RecordStore, RecordCursor, MemoryStore, MemoryCursor};
+pub use http::{Request, Response, ResponseFuture, Credentials, HttpClient, LocalServer};
+pub use websocket::{WebSocket, Message, CloseEvent, ReadyState, Socket, LocalEchoSocket};
+#[cfg(feature = "futures-core")]
+pub use websocket::MessageStream;
+pub use event_source::{EventSource, EventSourceState, ServerEvent};
+pub use css::{CssValue, Length, Color, Transform};
+pub use form::{InputElement, SelectElement, TextAreaElement, ValidityState};
+pub use router::{Router, RouterMode};
+pub use cookie::{CookieBuilder, SameSite};
+pub use dialog::DialogElement;
+pub use panic_hook::{PanicReport, set_panic_hook, set_panic_hook_with};
+
 pub struct HtmlNode<'a> {
     id: libc::c_int,
     doc: *const Document<'a>,
@@ -102,6 +134,34 @@ impl<'a> PartialEq for HtmlNode<'a> {
 }
 impl<'a> Eq for HtmlNode<'a> {}
 
+/// Element types an `HtmlNode` can be checked and converted into with
+/// `HtmlNode::dyn_into`.
+pub trait FromNode<'a>: Sized {
+    /// The name of the DOM interface, e.g. `HTMLInputElement`.
+    fn interface() -> &'static str;
+    fn from_node(node: HtmlNode<'a>) -> Self;
+}
+
+impl<'a> HtmlNode<'a> {
+    pub fn instance_of(&self, interface: &str) -> bool {
+        let is = js! { (self.id, interface) b"\
+            var ctor = window[UTF8ToString($1)];\
+            return +(typeof ctor == 'function' && WEBPLATFORM.rs_refs[$0] instanceof ctor);\
+        \0" };
+        is != 0
+    }
+
+    /// Converts this node into a typed element, or gives it back unchanged
+    /// if it isn't one.
+    pub fn dyn_into<T: FromNode<'a>>(self) -> Result<T, HtmlNode<'a>> {
+        if self.instance_of(T::interface()) {
+            Ok(T::from_node(self))
+        } else {
+            Err(self)
+        }
+    }
+}
+
 pub struct JSRef<'a> {
     ptr: *const HtmlNode<'a>,
 }
@@ -138,8 +198,9 @@ impl<'a> Deref for JSRef<'a> {
     }
 }
 
+/// An event passed to `on` handlers, with the node it was dispatched to.
 pub struct Event<'a> {
-    pub target: Option<HtmlNode<'a>>
+    pub target: Option<HtmlNode<'a>>,
 }
 
 extern fn rust_caller<'a, F: FnMut(Event<'a>)>(a: *const libc::c_void, docptr: *const libc::c_void, id: i32) {
@@ -152,11 +213,251 @@ extern fn rust_caller<'a, F: FnMut(Event<'a>)>(a: *const libc::c_void, docptr: *
                 id: id,
                 doc: unsafe { mem::transmute(docptr) },
             })
-        }
-        // target: None,
+        },
     });
 }
 
+// Shared by `HtmlNode::on`, `captured_on` and the other bindings that need
+// an event listener. The boxed handler lives as long as `doc`. While it runs,
+// the JS event is in `WEBPLATFORM.event` for bindings that read its fields.
+fn listen<'a, F: FnMut(Event<'a>) + 'a>(doc: *const Document<'a>, id: libc::c_int, s: &str, capture: bool, f: F) {
+    unsafe {
+        let b = Box::new(f);
+        let a = &*b as *const _;
+        js! { (id, s, a as *const libc::c_void,
+            rust_caller::<F> as *const libc::c_void,
+            doc as *const libc::c_void,
+            capture as i32)
+            b"\
+            WEBPLATFORM.rs_refs[$0].addEventListener(UTF8ToString($1), function (e) {\
+                var outer = WEBPLATFORM.event;\
+                WEBPLATFORM.event = e;\
+                try {\
+                    Runtime.dynCall('viii', $3, [$2, $4, e.target ? WEBPLATFORM.rs_refs.push(e.target) - 1 : -1]);\
+                } finally {\
+                    WEBPLATFORM.event = outer;\
+                }\
+            }, !!$5);\
+        \0" };
+        (&*doc).refs.borrow_mut().push(b);
+    }
+}
+
+fn string_list(id: libc::c_int) -> Vec<String> {
+    let len = js! { (id) b"\
+        return WEBPLATFORM.rs_refs[$0].length;\
+    \0" };
+    (0..len).map(|i| {
+        let a = js! { (id, i) b"\
+            var list = WEBPLATFORM.rs_refs[$0];\
+            var str = list.item ? list.item($1) : list[$1];\
+            return allocate(intArrayFromString(String(str)), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }).collect()
+}
+
+// Snippets that can throw catch the exception and return `-2 - id` of it
+// instead of an id, so -1 stays free to mean "none".
+fn exception_message(id: libc::c_int) -> String {
+    let a = js! { (-2 - id) b"\
+        var e = WEBPLATFORM.rs_refs[$0];\
+        return allocate(intArrayFromString(e.name + ': ' + e.message), 'i8', ALLOC_STACK);\
+    \0" };
+    unsafe {
+        str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+    }
+}
+
+// `user-id` -> `userId`, the form `dataset` expects. Like the DOM, only a
+// hyphen followed by a lowercase ASCII letter is folded away.
+fn data_key(s: &str) -> String {
+    let mut key = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(&next) if c == '-' && next.is_ascii_lowercase() => {
+                key.push(next.to_ascii_uppercase());
+                chars.next();
+            }
+            _ => key.push(c),
+        }
+    }
+    key
+}
+
+fn hex(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+// Decodes `%XX` escapes, leaving malformed ones as they are.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                out.push(hi << 4 | lo);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// Checks that `s` is a single JSON value, for the in-memory stand-ins, which
+// never pass it through the browser's `JSON.parse`.
+fn json_check(s: &str) -> Result<(), String> {
+    let b = s.as_bytes();
+    let mut i = json_value(b, json_space(b, 0))?;
+    i = json_space(b, i);
+    if i == b.len() {
+        Ok(())
+    } else {
+        Err(format!("unexpected character at position {}", i))
+    }
+}
+
+fn json_space(b: &[u8], mut i: usize) -> usize {
+    while i < b.len() && (b[i] == b' ' || b[i] == b'\t' || b[i] == b'\n' || b[i] == b'\r') {
+        i += 1;
+    }
+    i
+}
+
+fn json_value(b: &[u8], i: usize) -> Result<usize, String> {
+    let unexpected = |i: usize| if i < b.len() {
+        Err(format!("unexpected character at position {}", i))
+    } else {
+        Err("unexpected end of JSON input".to_owned())
+    };
+    match b.get(i) {
+        Some(&b'{') => {
+            let mut i = json_space(b, i + 1);
+            if b.get(i) == Some(&b'}') {
+                return Ok(i + 1);
+            }
+            loop {
+                if b.get(i) != Some(&b'"') {
+                    return unexpected(i);
+                }
+                i = json_space(b, json_value(b, i)?);
+                if b.get(i) != Some(&b':') {
+                    return unexpected(i);
+                }
+                i = json_space(b, json_value(b, json_space(b, i + 1))?);
+                match b.get(i) {
+                    Some(&b',') => i = json_space(b, i + 1),
+                    Some(&b'}') => return Ok(i + 1),
+                    _ => return unexpected(i),
+                }
+            }
+        }
+        Some(&b'[') => {
+            let mut i = json_space(b, i + 1);
+            if b.get(i) == Some(&b']') {
+                return Ok(i + 1);
+            }
+            loop {
+                i = json_space(b, json_value(b, i)?);
+                match b.get(i) {
+                    Some(&b',') => i = json_space(b, i + 1),
+                    Some(&b']') => return Ok(i + 1),
+                    _ => return unexpected(i),
+                }
+            }
+        }
+        Some(&b'"') => {
+            let mut i = i + 1;
+            loop {
+                match b.get(i) {
+                    Some(&b'"') => return Ok(i + 1),
+                    Some(&b'\\') => match b.get(i + 1) {
+                        Some(&b'u') if b.len() >= i + 6 && b[i + 2..i + 6].iter().all(|c| c.is_ascii_hexdigit()) => i += 6,
+                        Some(c) if b"\"\\/bfnrt".contains(c) => i += 2,
+                        _ => return unexpected(i + 1),
+                    },
+                    Some(&c) if c < 0x20 => return unexpected(i),
+                    Some(_) => i += 1,
+                    None => return unexpected(i),
+                }
+            }
+        }
+        Some(&b't') if b[i..].starts_with(b"true") => Ok(i + 4),
+        Some(&b'f') if b[i..].starts_with(b"false") => Ok(i + 5),
+        Some(&b'n') if b[i..].starts_with(b"null") => Ok(i + 4),
+        Some(&c) if c == b'-' || c.is_ascii_digit() => {
+            let digits = |mut i: usize| {
+                let start = i;
+                while i < b.len() && b[i].is_ascii_digit() {
+                    i += 1;
+                }
+                (i, i > start)
+            };
+            let mut i = if c == b'-' { i + 1 } else { i };
+            if b.get(i) == Some(&b'0') {
+                i += 1;
+            } else {
+                let (end, any) = digits(i);
+                if !any {
+                    return unexpected(i);
+                }
+                i = end;
+            }
+            if b.get(i) == Some(&b'.') {
+                let (end, any) = digits(i + 1);
+                if !any {
+                    return unexpected(end);
+                }
+                i = end;
+            }
+            if b.get(i) == Some(&b'e') || b.get(i) == Some(&b'E') {
+                i += 1;
+                if b.get(i) == Some(&b'+') || b.get(i) == Some(&b'-') {
+                    i += 1;
+                }
+                let (end, any) = digits(i);
+                if !any {
+                    return unexpected(end);
+                }
+                i = end;
+            }
+            Ok(i)
+        }
+        _ => unexpected(i),
+    }
+}
+
+// `userId` -> `user-id`, the form used in `data-*` attributes.
+fn data_attr(s: &str) -> String {
+    let mut attr = String::with_capacity(s.len() + 4);
+    for c in s.chars() {
+        if c.is_ascii_uppercase() {
+            attr.push('-');
+            attr.push(c.to_ascii_lowercase());
+        } else {
+            attr.push(c);
+        }
+    }
+    attr
+}
+
+// `backgroundColor` -> `background-color`; CSS names and custom properties
+// (`--name`) are returned unchanged.
+fn css_name(s: &str) -> String {
+    if s.starts_with("--") {
+        s.to_owned()
+    } else {
+        data_attr(s)
+    }
+}
+
 impl<'a> HtmlNode<'a> {
     pub fn element_query(&self, s: &str) -> Option<HtmlNode<'a>> {
         let id = js! { (self.id, s) b"\
@@ -233,14 +534,29 @@ impl<'a> HtmlNode<'a> {
         }
     }
 
-    pub fn class_get(&self) -> HashSet<String> {
+    pub fn text_set(&self, s: &str) {
+        js! { (self.id, s) b"\
+            WEBPLATFORM.rs_refs[$0].textContent = UTF8ToString($1);\
+        \0" };
+    }
+
+    pub fn text_get(&self) -> String {
         let a = js! { (self.id) b"\
-            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].className), 'i8', ALLOC_STACK);\
+            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].textContent || ''), 'i8', ALLOC_STACK);\
         \0" };
-        let class = unsafe {
+        unsafe {
             str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    pub fn class_get(&self) -> HashSet<String> {
+        let a = js! { (self.id) b"\
+            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].classList.value), 'i8', ALLOC_STACK);\
+        \0" };
+        let list = unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap()
         };
-        class.trim().split(char::is_whitespace).map(|x| x.to_string()).collect()
+        list.split_whitespace().map(|x| x.to_owned()).collect()
     }
 
     pub fn class_add(&self, s: &str) {
@@ -255,6 +571,48 @@ impl<'a> HtmlNode<'a> {
         \0" };
     }
 
+    /// Adds several classes in one call; names must not contain whitespace.
+    pub fn class_add_many(&self, names: &[&str]) {
+        js! { (self.id, &names.join(" ")[..]) b"\
+            var list = WEBPLATFORM.rs_refs[$0].classList;\
+            list.add.apply(list, UTF8ToString($1).split(' ').filter(Boolean));\
+        \0" };
+    }
+
+    /// Removes several classes in one call; names must not contain whitespace.
+    pub fn class_remove_many(&self, names: &[&str]) {
+        js! { (self.id, &names.join(" ")[..]) b"\
+            var list = WEBPLATFORM.rs_refs[$0].classList;\
+            list.remove.apply(list, UTF8ToString($1).split(' ').filter(Boolean));\
+        \0" };
+    }
+
+    /// Returns whether the class is now present.
+    pub fn class_toggle(&self, s: &str, force: Option<bool>) -> bool {
+        let on = js! { (self.id, s, force.map(|f| f as i32).unwrap_or(-1)) b"\
+            var list = WEBPLATFORM.rs_refs[$0].classList;\
+            var name = UTF8ToString($1);\
+            return +($2 < 0 ? list.toggle(name) : list.toggle(name, !!$2));\
+        \0" };
+        on != 0
+    }
+
+    pub fn class_contains(&self, s: &str) -> bool {
+        let contains = js! { (self.id, s) b"\
+            return +(WEBPLATFORM.rs_refs[$0].classList.contains(UTF8ToString($1)));\
+        \0" };
+        contains != 0
+    }
+
+    /// Swaps `old` for `new` in place, keeping its position in the list.
+    /// Returns false if `old` wasn't present, in which case nothing changes.
+    pub fn class_replace(&self, old: &str, new: &str) -> bool {
+        let replaced = js! { (self.id, old, new) b"\
+            return +WEBPLATFORM.rs_refs[$0].classList.replace(UTF8ToString($1), UTF8ToString($2));\
+        \0" };
+        replaced != 0
+    }
+
     pub fn parent(&self) -> Option<HtmlNode<'a>> {
         let id = js! { (self.id) b"\
             var value = WEBPLATFORM.rs_refs[$0].parentNode;\
@@ -273,14 +631,138 @@ impl<'a> HtmlNode<'a> {
         }
     }
 
+    fn node_get(&self, s: &str) -> Option<HtmlNode<'a>> {
+        let id = js! { (self.id, s) b"\
+            var value = WEBPLATFORM.rs_refs[$0][UTF8ToString($1)];\
+            if (!value) {\
+                return -1;\
+            }\
+            return WEBPLATFORM.rs_refs.push(value) - 1;\
+        \0" };
+        if id < 0 {
+            None
+        } else {
+            Some(HtmlNode {
+                id: id,
+                doc: self.doc,
+            })
+        }
+    }
+
+    pub fn clone_node(&self, deep: bool) -> HtmlNode<'a> {
+        let id = js! { (self.id, deep as i32) b"\
+            return WEBPLATFORM.rs_refs.push(WEBPLATFORM.rs_refs[$0].cloneNode(!!$1)) - 1;\
+        \0" };
+        HtmlNode {
+            id: id,
+            doc: self.doc,
+        }
+    }
+
+    /// The document fragment holding the contents of a `<template>` element,
+    /// `None` for any other element.
+    pub fn template_content(&self) -> Option<HtmlNode<'a>> {
+        if !self.instance_of("HTMLTemplateElement") {
+            return None;
+        }
+        self.node_get("content")
+    }
+
+    pub fn first_child(&self) -> Option<HtmlNode<'a>> {
+        self.node_get("firstChild")
+    }
+
+    pub fn last_child(&self) -> Option<HtmlNode<'a>> {
+        self.node_get("lastChild")
+    }
+
+    pub fn next_sibling(&self) -> Option<HtmlNode<'a>> {
+        self.node_get("nextSibling")
+    }
+
+    pub fn previous_sibling(&self) -> Option<HtmlNode<'a>> {
+        self.node_get("previousSibling")
+    }
+
+    pub fn first_element_child(&self) -> Option<HtmlNode<'a>> {
+        self.node_get("firstElementChild")
+    }
+
+    pub fn last_element_child(&self) -> Option<HtmlNode<'a>> {
+        self.node_get("lastElementChild")
+    }
+
+    pub fn next_element_sibling(&self) -> Option<HtmlNode<'a>> {
+        self.node_get("nextElementSibling")
+    }
+
+    pub fn previous_element_sibling(&self) -> Option<HtmlNode<'a>> {
+        self.node_get("previousElementSibling")
+    }
+
+    pub fn parent_element(&self) -> Option<HtmlNode<'a>> {
+        self.node_get("parentElement")
+    }
+
+    /// Iterates over child elements, walking siblings one at a time.
+    pub fn children(&self) -> NodeIter<'a> {
+        NodeIter {
+            next: self.first_element_child(),
+            elements: true,
+        }
+    }
+
+    /// Iterates over all child nodes, including text and comments.
+    pub fn child_nodes(&self) -> NodeIter<'a> {
+        NodeIter {
+            next: self.first_child(),
+            elements: false,
+        }
+    }
+
+    pub fn closest(&self, s: &str) -> Option<HtmlNode<'a>> {
+        let id = js! { (self.id, s) b"\
+            var value = WEBPLATFORM.rs_refs[$0].closest(UTF8ToString($1));\
+            if (!value) {\
+                return -1;\
+            }\
+            return WEBPLATFORM.rs_refs.push(value) - 1;\
+        \0" };
+        if id < 0 {
+            None
+        } else {
+            Some(HtmlNode {
+                id: id,
+                doc: self.doc,
+            })
+        }
+    }
+
+    pub fn matches(&self, s: &str) -> bool {
+        let matches = js! { (self.id, s) b"\
+            return +(WEBPLATFORM.rs_refs[$0].matches(UTF8ToString($1)));\
+        \0" };
+        matches != 0
+    }
+
+    /// True if `node` is this node or one of its descendants.
+    pub fn contains(&self, node: &HtmlNode) -> bool {
+        let contains = js! { (self.id, node.id) b"\
+            return +(WEBPLATFORM.rs_refs[$0].contains(WEBPLATFORM.rs_refs[$1]));\
+        \0" };
+        contains != 0
+    }
+
+    // Data keys may be given either as in the attribute (`user-id`) or as in
+    // `dataset` (`userId`).
     pub fn data_set(&self, s: &str, v: &str) {
-        js! { (self.id, s, v) b"\
+        js! { (self.id, &data_key(s)[..], v) b"\
             WEBPLATFORM.rs_refs[$0].dataset[UTF8ToString($1)] = UTF8ToString($2);\
         \0" };
     }
 
     pub fn data_get(&self, s: &str) -> Option<String> {
-        let a = js! { (self.id, s) b"\
+        let a = js! { (self.id, &data_key(s)[..]) b"\
             var str = WEBPLATFORM.rs_refs[$0].dataset[UTF8ToString($1)];\
             if (str == null) return -1;\
             return allocate(intArrayFromString(str), 'i8', ALLOC_STACK);\
@@ -294,6 +776,26 @@ impl<'a> HtmlNode<'a> {
         }
     }
 
+    pub fn data_remove(&self, s: &str) {
+        js! { (self.id, &data_key(s)[..]) b"\
+            delete WEBPLATFORM.rs_refs[$0].dataset[UTF8ToString($1)];\
+        \0" };
+    }
+
+    /// Keys are returned as in the attribute, e.g. `user-id` for `data-user-id`.
+    pub fn data_keys(&self) -> Vec<String> {
+        let keys = js! { (self.id) b"\
+            return WEBPLATFORM.rs_refs.push(Object.keys(WEBPLATFORM.rs_refs[$0].dataset)) - 1;\
+        \0" };
+        string_list(keys).iter().map(|k| data_attr(k)).collect()
+    }
+
+    pub fn data_all(&self) -> HashMap<String, String> {
+        self.data_keys().into_iter().filter_map(|k| {
+            self.data_get(&k).map(|v| (k, v))
+        }).collect()
+    }
+
     pub fn style_set_str(&self, s: &str, v: &str) {
         js! { (self.id, s, v) b"\
             WEBPLATFORM.rs_refs[$0].style[UTF8ToString($1)] = UTF8ToString($2);\
@@ -309,6 +811,101 @@ impl<'a> HtmlNode<'a> {
         }
     }
 
+    pub fn style_set<V: CssValue>(&self, s: &str, v: V) -> Result<(), String> {
+        self.style_set_many(&[(s, &v)]).map_err(|mut invalid| invalid.remove(0))
+    }
+
+    /// Sets several properties in one call, by camelCase or CSS name; custom
+    /// properties (`--name`) are used as given. Properties whose value the
+    /// browser rejected keep their old value and are returned by name.
+    pub fn style_set_many(&self, props: &[(&str, &dyn CssValue)]) -> Result<(), Vec<String>> {
+        if props.is_empty() {
+            return Ok(());
+        }
+        // Property/value pairs are packed with the ASCII unit and record
+        // separators, which can't appear in valid CSS.
+        let packed = props.iter()
+            .map(|&(name, value)| format!("{}\x1f{}", css_name(name), value.to_css()))
+            .collect::<Vec<_>>()
+            .join("\x1e");
+        let a = js! { (self.id, &packed[..]) b"\
+            var style = WEBPLATFORM.rs_refs[$0].style;\
+            var invalid = [];\
+            UTF8ToString($1).split('\\x1e').forEach(function (pair, i) {\
+                pair = pair.split('\\x1f');\
+                var name = pair[0];\
+                var old = style.getPropertyValue(name);\
+                var priority = style.getPropertyPriority(name);\
+                style.removeProperty(name);\
+                style.setProperty(name, pair[1]);\
+                if (pair[1] !== '' && style.getPropertyValue(name) === '') {\
+                    style.setProperty(name, old, priority);\
+                    invalid.push(i);\
+                }\
+            });\
+            return allocate(intArrayFromString(invalid.join(',')), 'i8', ALLOC_STACK);\
+        \0" };
+        let invalid = unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        };
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid.split(',').map(|i| props[i.parse::<usize>().unwrap()].0.to_owned()).collect())
+        }
+    }
+
+    /// Takes the camelCase or CSS property name.
+    pub fn style_remove(&self, s: &str) {
+        let s = &css_name(s)[..];
+        js! { (self.id, s) b"\
+            WEBPLATFORM.rs_refs[$0].style.removeProperty(UTF8ToString($1));\
+        \0" };
+    }
+
+    /// Like `style_set_str`, but with an optional `!important` priority.
+    pub fn style_set_priority(&self, s: &str, v: &str, important: bool) {
+        let s = &css_name(s)[..];
+        js! { (self.id, s, v, important as i32) b"\
+            WEBPLATFORM.rs_refs[$0].style.setProperty(UTF8ToString($1), UTF8ToString($2), $3 ? 'important' : '');\
+        \0" };
+    }
+
+    /// The resolved value of a CSS property after stylesheets are applied.
+    /// Takes the camelCase or CSS property name.
+    pub fn computed_style(&self, s: &str) -> String {
+        let s = &css_name(s)[..];
+        let a = js! { (self.id, s) b"\
+            var style = window.getComputedStyle(WEBPLATFORM.rs_refs[$0]);\
+            return allocate(intArrayFromString(style.getPropertyValue(UTF8ToString($1))), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    /// Reads a custom property as seen by this element, including inherited
+    /// values. The leading `--` is optional.
+    pub fn css_var_get(&self, s: &str) -> String {
+        let a = js! { (self.id, s) b"\
+            var name = UTF8ToString($1);\
+            if (name.indexOf('--') != 0) name = '--' + name;\
+            var style = window.getComputedStyle(WEBPLATFORM.rs_refs[$0]);\
+            return allocate(intArrayFromString(style.getPropertyValue(name).trim()), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    pub fn css_var_set(&self, s: &str, v: &str) {
+        js! { (self.id, s, v) b"\
+            var name = UTF8ToString($1);\
+            if (name.indexOf('--') != 0) name = '--' + name;\
+            WEBPLATFORM.rs_refs[$0].style.setProperty(name, UTF8ToString($2));\
+        \0" };
+    }
+
     pub fn prop_set_i32(&self, s: &str, v: i32) {
         js! { (self.id, s, v) b"\
             WEBPLATFORM.rs_refs[$0][UTF8ToString($1)] = $2;\
@@ -327,9 +924,100 @@ impl<'a> HtmlNode<'a> {
         \0" };
     }
 
+    pub fn prop_get_f64(&self, s: &str) -> f64 {
+        let mut v: f64 = 0.0;
+        let v_ptr: *mut f64 = &mut v;
+        js! { (self.id, s, v_ptr as *const libc::c_void) b"\
+            setValue($2, Number(WEBPLATFORM.rs_refs[$0][UTF8ToString($1)]), 'double');\
+        \0" };
+        v
+    }
+
+    pub fn bounding_client_rect(&self) -> Rect {
+        let mut r = [0f64; 4];
+        js! { (self.id, r.as_mut_ptr() as *const libc::c_void) b"\
+            var rect = WEBPLATFORM.rs_refs[$0].getBoundingClientRect();\
+            setValue($1, rect.left, 'double');\
+            setValue($1 + 8, rect.top, 'double');\
+            setValue($1 + 16, rect.width, 'double');\
+            setValue($1 + 24, rect.height, 'double');\
+        \0" };
+        Rect {
+            x: r[0],
+            y: r[1],
+            width: r[2],
+            height: r[3],
+        }
+    }
+
+    pub fn client_width(&self) -> f64 {
+        self.prop_get_f64("clientWidth")
+    }
+
+    pub fn client_height(&self) -> f64 {
+        self.prop_get_f64("clientHeight")
+    }
+
+    pub fn offset_width(&self) -> f64 {
+        self.prop_get_f64("offsetWidth")
+    }
+
+    pub fn offset_height(&self) -> f64 {
+        self.prop_get_f64("offsetHeight")
+    }
+
+    pub fn offset_left(&self) -> f64 {
+        self.prop_get_f64("offsetLeft")
+    }
+
+    pub fn offset_top(&self) -> f64 {
+        self.prop_get_f64("offsetTop")
+    }
+
+    pub fn scroll_width(&self) -> f64 {
+        self.prop_get_f64("scrollWidth")
+    }
+
+    pub fn scroll_height(&self) -> f64 {
+        self.prop_get_f64("scrollHeight")
+    }
+
+    pub fn scroll_left(&self) -> f64 {
+        self.prop_get_f64("scrollLeft")
+    }
+
+    pub fn scroll_top(&self) -> f64 {
+        self.prop_get_f64("scrollTop")
+    }
+
+    pub fn scroll_to(&self, x: f64, y: f64, smooth: bool) {
+        let pos = [x, y];
+        js! { (self.id, pos.as_ptr() as *const libc::c_void, smooth as i32) b"\
+            var el = WEBPLATFORM.rs_refs[$0];\
+            var x = getValue($1, 'double');\
+            var y = getValue($1 + 8, 'double');\
+            if (el.scrollTo) {\
+                el.scrollTo({ left: x, top: y, behavior: $2 ? 'smooth' : 'auto' });\
+            } else {\
+                el.scrollLeft = x;\
+                el.scrollTop = y;\
+            }\
+        \0" };
+    }
+
+    pub fn scroll_into_view(&self, smooth: bool, block: ScrollAlign, inline: ScrollAlign) {
+        js! { (self.id, smooth as i32, block.as_str(), inline.as_str()) b"\
+            WEBPLATFORM.rs_refs[$0].scrollIntoView({\
+                behavior: $1 ? 'smooth' : 'auto',\
+                block: UTF8ToString($2),\
+                inline: UTF8ToString($3)\
+            });\
+        \0" };
+    }
+
     pub fn prop_get_str(&self, s: &str) -> String {
         let a = js! { (self.id, s) b"\
-            var a = allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0][UTF8ToString($1)] || ''), 'i8', ALLOC_STACK); console.log(WEBPLATFORM.rs_refs[$0]); return a;\
+            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0][UTF8ToString($1)] || ''), 'i8', ALLOC_STACK);\
         \0" };
         unsafe {
             str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
@@ -356,61 +1044,189 @@ impl<'a> HtmlNode<'a> {
 
     pub fn attr_get_str(&self, s: &str) -> String {
         let a = js! { (self.id, s) b"\
-            var a = allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].getAttribute(UTF8ToString($1)) || ''), 'i8', ALLOC_STACK); console.log(WEBPLATFORM.rs_refs[$0]); return a;\
+            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].getAttribute(UTF8ToString($1)) || ''), 'i8', ALLOC_STACK);\
         \0" };
         unsafe {
             str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
         }
     }
 
-    pub fn append(&self, s: &HtmlNode) {
-        js! { (self.id, s.id) b"\
-            WEBPLATFORM.rs_refs[$0].appendChild(WEBPLATFORM.rs_refs[$1]);\
+    pub fn attr_get(&self, s: &str) -> Option<String> {
+        let a = js! { (self.id, s) b"\
+            var str = WEBPLATFORM.rs_refs[$0].getAttribute(UTF8ToString($1));\
+            if (str == null) return -1;\
+            return allocate(intArrayFromString(str), 'i8', ALLOC_STACK);\
         \0" };
+        if a == -1 {
+            None
+        } else {
+            Some(unsafe {
+                str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+            })
+        }
     }
 
-    pub fn html_append(&self, s: &str) {
-        js! { (self.id, s) b"\
-            WEBPLATFORM.rs_refs[$0].insertAdjacentHTML('beforeEnd', UTF8ToString($1));\
+    pub fn attr_has(&self, s: &str) -> bool {
+        let has = js! { (self.id, s) b"\
+            return +(WEBPLATFORM.rs_refs[$0].hasAttribute(UTF8ToString($1)));\
         \0" };
+        has != 0
     }
 
-    pub fn html_prepend(&self, s: &str) {
+    pub fn attr_remove(&self, s: &str) {
         js! { (self.id, s) b"\
-            WEBPLATFORM.rs_refs[$0].insertAdjacentHTML('afterBegin', UTF8ToString($1));\
+            WEBPLATFORM.rs_refs[$0].removeAttribute(UTF8ToString($1));\
         \0" };
     }
 
-    pub fn on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) {
-        unsafe {
-            let b = Box::new(f);
-            let a = &*b as *const _;
-            js! { (self.id, s, a as *const libc::c_void,
-                rust_caller::<F> as *const libc::c_void,
-                self.doc as *const libc::c_void)
-                b"\
-                WEBPLATFORM.rs_refs[$0].addEventListener(UTF8ToString($1), function (e) {\
-                    Runtime.dynCall('viii', $3, [$2, $4, e.target ? WEBPLATFORM.rs_refs.push(e.target) - 1 : -1]);\
-                }, false);\
-            \0" };
-            (&*self.doc).refs.borrow_mut().push(b);
+    /// Adds the boolean attribute `s` if absent and removes it if present,
+    /// or forces it on or off. Returns whether the attribute is now set.
+    pub fn attr_toggle(&self, s: &str, force: Option<bool>) -> bool {
+        let on = js! { (self.id, s, force.map(|f| f as i32).unwrap_or(-1)) b"\
+            var el = WEBPLATFORM.rs_refs[$0];\
+            var name = UTF8ToString($1);\
+            var on = $2 < 0 ? !el.hasAttribute(name) : !!$2;\
+            if (!on) {\
+                el.removeAttribute(name);\
+            } else if (!el.hasAttribute(name)) {\
+                el.setAttribute(name, '');\
+            }\
+            return +on;\
+        \0" };
+        on != 0
+    }
+
+    /// All attributes as (name, value) pairs, in document order.
+    pub fn attrs(&self) -> Vec<(String, String)> {
+        let names = js! { (self.id) b"\
+            var attrs = WEBPLATFORM.rs_refs[$0].attributes;\
+            var names = [];\
+            for (var i = 0; i < attrs.length; i++) {\
+                names.push(attrs[i].name);\
+            }\
+            return WEBPLATFORM.rs_refs.push(names) - 1;\
+        \0" };
+        let values = js! { (self.id) b"\
+            var attrs = WEBPLATFORM.rs_refs[$0].attributes;\
+            var values = [];\
+            for (var i = 0; i < attrs.length; i++) {\
+                values.push(attrs[i].value);\
+            }\
+            return WEBPLATFORM.rs_refs.push(values) - 1;\
+        \0" };
+        string_list(names).into_iter().zip(string_list(values)).collect()
+    }
+
+    /// Sets a namespaced attribute, e.g. `xlink:href` on SVG elements.
+    pub fn attr_set_ns(&self, ns: &str, s: &str, v: &str) {
+        js! { (self.id, ns, s, v) b"\
+            WEBPLATFORM.rs_refs[$0].setAttributeNS(UTF8ToString($1), UTF8ToString($2), UTF8ToString($3));\
+        \0" };
+    }
+
+    pub fn attr_get_ns(&self, ns: &str, s: &str) -> Option<String> {
+        let a = js! { (self.id, ns, s) b"\
+            var str = WEBPLATFORM.rs_refs[$0].getAttributeNS(UTF8ToString($1), UTF8ToString($2));\
+            if (str == null) return -1;\
+            return allocate(intArrayFromString(str), 'i8', ALLOC_STACK);\
+        \0" };
+        if a == -1 {
+            None
+        } else {
+            Some(unsafe {
+                str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+            })
         }
     }
 
+    pub fn attr_remove_ns(&self, ns: &str, s: &str) {
+        js! { (self.id, ns, s) b"\
+            WEBPLATFORM.rs_refs[$0].removeAttributeNS(UTF8ToString($1), UTF8ToString($2));\
+        \0" };
+    }
+
+    pub fn append(&self, s: &HtmlNode) {
+        js! { (self.id, s.id) b"\
+            WEBPLATFORM.rs_refs[$0].appendChild(WEBPLATFORM.rs_refs[$1]);\
+        \0" };
+    }
+
+    pub fn prepend(&self, s: &HtmlNode) {
+        js! { (self.id, s.id) b"\
+            var parent = WEBPLATFORM.rs_refs[$0];\
+            parent.insertBefore(WEBPLATFORM.rs_refs[$1], parent.firstChild);\
+        \0" };
+    }
+
+    /// Inserts `s` as a child before `reference`, or at the end if `None`.
+    pub fn insert_before(&self, s: &HtmlNode, reference: Option<&HtmlNode>) {
+        js! { (self.id, s.id, reference.map(|r| r.id).unwrap_or(-1)) b"\
+            WEBPLATFORM.rs_refs[$0].insertBefore(WEBPLATFORM.rs_refs[$1], $2 < 0 ? null : WEBPLATFORM.rs_refs[$2]);\
+        \0" };
+    }
+
+    /// Inserts `s` as a child after `reference`, or at the start if `None`.
+    pub fn insert_after(&self, s: &HtmlNode, reference: Option<&HtmlNode>) {
+        js! { (self.id, s.id, reference.map(|r| r.id).unwrap_or(-1)) b"\
+            var parent = WEBPLATFORM.rs_refs[$0];\
+            parent.insertBefore(WEBPLATFORM.rs_refs[$1], $2 < 0 ? parent.firstChild : WEBPLATFORM.rs_refs[$2].nextSibling);\
+        \0" };
+    }
+
+    /// Replaces the child `old` with `s`.
+    pub fn replace_child(&self, s: &HtmlNode, old: &HtmlNode) {
+        js! { (self.id, s.id, old.id) b"\
+            WEBPLATFORM.rs_refs[$0].replaceChild(WEBPLATFORM.rs_refs[$1], WEBPLATFORM.rs_refs[$2]);\
+        \0" };
+    }
+
+    pub fn replace_with(&self, s: &HtmlNode) {
+        js! { (self.id, s.id) b"\
+            var node = WEBPLATFORM.rs_refs[$0];\
+            node.parentNode.replaceChild(WEBPLATFORM.rs_refs[$1], node);\
+        \0" };
+    }
+
+    /// Inserts `s` as the previous sibling of this node.
+    pub fn before(&self, s: &HtmlNode) {
+        js! { (self.id, s.id) b"\
+            var node = WEBPLATFORM.rs_refs[$0];\
+            node.parentNode.insertBefore(WEBPLATFORM.rs_refs[$1], node);\
+        \0" };
+    }
+
+    /// Inserts `s` as the next sibling of this node.
+    pub fn after(&self, s: &HtmlNode) {
+        js! { (self.id, s.id) b"\
+            var node = WEBPLATFORM.rs_refs[$0];\
+            node.parentNode.insertBefore(WEBPLATFORM.rs_refs[$1], node.nextSibling);\
+        \0" };
+    }
+
+    pub fn insert_adjacent_element(&self, position: Position, s: &HtmlNode) {
+        js! { (self.id, position.as_str(), s.id) b"\
+            WEBPLATFORM.rs_refs[$0].insertAdjacentElement(UTF8ToString($1), WEBPLATFORM.rs_refs[$2]);\
+        \0" };
+    }
+
+    pub fn html_append(&self, s: &str) {
+        js! { (self.id, s) b"\
+            WEBPLATFORM.rs_refs[$0].insertAdjacentHTML('beforeEnd', UTF8ToString($1));\
+        \0" };
+    }
+
+    pub fn html_prepend(&self, s: &str) {
+        js! { (self.id, s) b"\
+            WEBPLATFORM.rs_refs[$0].insertAdjacentHTML('afterBegin', UTF8ToString($1));\
+        \0" };
+    }
+
+    pub fn on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) {
+        listen(self.doc, self.id, s, false, f);
+    }
+
     pub fn captured_on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) {
-        unsafe {
-            let b = Box::new(f);
-            let a = &*b as *const _;
-            js! { (self.id, s, a as *const libc::c_void,
-                rust_caller::<F> as *const libc::c_void,
-                self.doc as *const libc::c_void)
-                b"\
-                WEBPLATFORM.rs_refs[$0].addEventListener(UTF8ToString($1), function (e) {\
-                    Runtime.dynCall('viii', $3, [$2, $4, e.target ? WEBPLATFORM.rs_refs.push(e.target) - 1 : -1]);\
-                }, true);\
-            \0" };
-            (&*self.doc).refs.borrow_mut().push(b);
-        }
+        listen(self.doc, self.id, s, true, f);
     }
 
     pub fn remove_self(&self) {
@@ -421,12 +1237,119 @@ impl<'a> HtmlNode<'a> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub fn left(&self) -> f64 {
+        self.x
+    }
+
+    pub fn top(&self) -> f64 {
+        self.y
+    }
+
+    pub fn right(&self) -> f64 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollAlign {
+    Start,
+    Center,
+    End,
+    Nearest,
+}
+
+impl ScrollAlign {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            ScrollAlign::Start => "start",
+            ScrollAlign::Center => "center",
+            ScrollAlign::End => "end",
+            ScrollAlign::Nearest => "nearest",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    BeforeBegin,
+    AfterBegin,
+    BeforeEnd,
+    AfterEnd,
+}
+
+impl Position {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Position::BeforeBegin => "beforebegin",
+            Position::AfterBegin => "afterbegin",
+            Position::BeforeEnd => "beforeend",
+            Position::AfterEnd => "afterend",
+        }
+    }
+}
+
+pub struct NodeIter<'a> {
+    next: Option<HtmlNode<'a>>,
+    elements: bool,
+}
+
+impl<'a> Iterator for NodeIter<'a> {
+    type Item = HtmlNode<'a>;
+    fn next(&mut self) -> Option<HtmlNode<'a>> {
+        let node = self.next.take();
+        if let Some(ref node) = node {
+            self.next = if self.elements {
+                node.next_element_sibling()
+            } else {
+                node.next_sibling()
+            };
+        }
+        node
+    }
+}
+
 pub fn alert(s: &str) {
     js! { (s) b"\
         alert(UTF8ToString($0));\
     \0" };
 }
 
+pub fn confirm(s: &str) -> bool {
+    let ok = js! { (s) b"\
+        return +confirm(UTF8ToString($0));\
+    \0" };
+    ok != 0
+}
+
+/// Returns `None` if the user cancels.
+pub fn prompt(s: &str, default: &str) -> Option<String> {
+    let a = js! { (s, default) b"\
+        var str = prompt(UTF8ToString($0), UTF8ToString($1));\
+        if (str == null) return -1;\
+        return allocate(intArrayFromString(str), 'i8', ALLOC_STACK);\
+    \0" };
+    if a == -1 {
+        None
+    } else {
+        Some(unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        })
+    }
+}
+
 pub struct Document<'a> {
     refs: Rc<RefCell<Vec<Box<FnMut(Event<'a>) + 'a>>>>,
 }
@@ -451,6 +1374,194 @@ impl<'a> Document<'a> {
         }
     }
 
+    pub fn text_create<'b>(&'b self, s: &str) -> HtmlNode<'a> {
+        let id = js! { (s) b"\
+            return WEBPLATFORM.rs_refs.push(document.createTextNode(UTF8ToString($0))) - 1;\
+        \0" };
+        HtmlNode {
+            id: id,
+            doc: &*self,
+        }
+    }
+
+    pub fn comment_create<'b>(&'b self, s: &str) -> HtmlNode<'a> {
+        let id = js! { (s) b"\
+            return WEBPLATFORM.rs_refs.push(document.createComment(UTF8ToString($0))) - 1;\
+        \0" };
+        HtmlNode {
+            id: id,
+            doc: &*self,
+        }
+    }
+
+    pub fn fragment_create<'b>(&'b self) -> HtmlNode<'a> {
+        let id = js! { b"\
+            return WEBPLATFORM.rs_refs.push(document.createDocumentFragment()) - 1;\
+        \0" };
+        HtmlNode {
+            id: id,
+            doc: &*self,
+        }
+    }
+
+    fn node_get<'b>(&'b self, s: &str) -> Option<HtmlNode<'a>> {
+        let id = js! { (s) b"\
+            var value = document[UTF8ToString($0)];\
+            if (!value) {\
+                return -1;\
+            }\
+            return WEBPLATFORM.rs_refs.push(value) - 1;\
+        \0" };
+
+        if id < 0 {
+            None
+        } else {
+            Some(HtmlNode {
+                id: id,
+                doc: self,
+            })
+        }
+    }
+
+    pub fn body<'b>(&'b self) -> Option<HtmlNode<'a>> {
+        self.node_get("body")
+    }
+
+    pub fn head<'b>(&'b self) -> Option<HtmlNode<'a>> {
+        self.node_get("head")
+    }
+
+    pub fn document_element<'b>(&'b self) -> Option<HtmlNode<'a>> {
+        self.node_get("documentElement")
+    }
+
+    /// The focused element, usually `body` when nothing is focused.
+    pub fn active_element<'b>(&'b self) -> Option<HtmlNode<'a>> {
+        self.node_get("activeElement")
+    }
+
+    pub fn title(&self) -> String {
+        let a = js! { b"\
+            return allocate(intArrayFromString(document.title), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    pub fn set_title(&self, s: &str) {
+        js! { (s) b"\
+            document.title = UTF8ToString($0);\
+        \0" };
+    }
+
+    pub fn ready_state(&self) -> DocumentReadyState {
+        let state = js! { b"\
+            return ['loading', 'interactive', 'complete'].indexOf(document.readyState);\
+        \0" };
+        match state {
+            0 => DocumentReadyState::Loading,
+            1 => DocumentReadyState::Interactive,
+            _ => DocumentReadyState::Complete,
+        }
+    }
+
+    pub fn referrer(&self) -> String {
+        let a = js! { b"\
+            return allocate(intArrayFromString(document.referrer), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    pub fn character_set(&self) -> String {
+        let a = js! { b"\
+            return allocate(intArrayFromString(document.characterSet), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    /// Calls `f` once the DOM is parsed: right away if it already is,
+    /// otherwise on `DOMContentLoaded`.
+    pub fn on_ready<F: FnOnce() + 'a>(&self, f: F) {
+        if self.ready_state() != DocumentReadyState::Loading {
+            f();
+            return;
+        }
+        let mut f = Some(f);
+        self.on("DOMContentLoaded", move |_| {
+            if let Some(f) = f.take() {
+                f();
+            }
+        });
+    }
+
+    /// Copies a node from another document (such as an iframe's, or a
+    /// `<template>`'s content) into this one.
+    pub fn import_node<'b>(&'b self, node: &HtmlNode, deep: bool) -> HtmlNode<'a> {
+        let id = js! { (node.id, deep as i32) b"\
+            return WEBPLATFORM.rs_refs.push(document.importNode(WEBPLATFORM.rs_refs[$0], !!$1)) - 1;\
+        \0" };
+        HtmlNode {
+            id: id,
+            doc: &*self,
+        }
+    }
+
+    pub fn scroll_x(&self) -> f64 {
+        let mut v: f64 = 0.0;
+        let v_ptr: *mut f64 = &mut v;
+        js! { (v_ptr as *const libc::c_void) b"\
+            setValue($0, window.pageXOffset, 'double');\
+        \0" };
+        v
+    }
+
+    pub fn scroll_y(&self) -> f64 {
+        let mut v: f64 = 0.0;
+        let v_ptr: *mut f64 = &mut v;
+        js! { (v_ptr as *const libc::c_void) b"\
+            setValue($0, window.pageYOffset, 'double');\
+        \0" };
+        v
+    }
+
+    /// Scrolls the window.
+    pub fn scroll_to(&self, x: f64, y: f64, smooth: bool) {
+        let pos = [x, y];
+        js! { (pos.as_ptr() as *const libc::c_void, smooth as i32) b"\
+            window.scrollTo({\
+                left: getValue($0, 'double'),\
+                top: getValue($0 + 8, 'double'),\
+                behavior: $1 ? 'smooth' : 'auto'\
+            });\
+        \0" };
+    }
+
+    /// Reads a custom property from `:root`. The leading `--` is optional.
+    pub fn css_var_get(&self, s: &str) -> String {
+        let a = js! { (s) b"\
+            var name = UTF8ToString($0);\
+            if (name.indexOf('--') != 0) name = '--' + name;\
+            var style = window.getComputedStyle(document.documentElement);\
+            return allocate(intArrayFromString(style.getPropertyValue(name).trim()), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    pub fn css_var_set(&self, s: &str, v: &str) {
+        js! { (s, v) b"\
+            var name = UTF8ToString($0);\
+            if (name.indexOf('--') != 0) name = '--' + name;\
+            document.documentElement.style.setProperty(name, UTF8ToString($1));\
+        \0" };
+    }
+
     pub fn location_hash_get(&self) -> String {
         let a = js! { b"\
             return allocate(intArrayFromString(window.location.hash), 'i8', ALLOC_STACK);\
@@ -460,6 +1571,183 @@ impl<'a> Document<'a> {
         }
     }
 
+    fn location_get(&self, s: &str) -> String {
+        let a = js! { (s) b"\
+            return allocate(intArrayFromString(window.location[UTF8ToString($0)]), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    pub fn location_href_get(&self) -> String {
+        self.location_get("href")
+    }
+
+    pub fn location_protocol_get(&self) -> String {
+        self.location_get("protocol")
+    }
+
+    pub fn location_host_get(&self) -> String {
+        self.location_get("host")
+    }
+
+    pub fn location_hostname_get(&self) -> String {
+        self.location_get("hostname")
+    }
+
+    pub fn location_port_get(&self) -> String {
+        self.location_get("port")
+    }
+
+    pub fn location_pathname_get(&self) -> String {
+        self.location_get("pathname")
+    }
+
+    pub fn location_search_get(&self) -> String {
+        self.location_get("search")
+    }
+
+    pub fn location_origin_get(&self) -> String {
+        self.location_get("origin")
+    }
+
+    pub fn location_hash_set(&self, s: &str) {
+        js! { (s) b"\
+            window.location.hash = UTF8ToString($0);\
+        \0" };
+    }
+
+    /// Navigates to `s`, like `location_assign`.
+    pub fn location_href_set(&self, s: &str) {
+        js! { (s) b"\
+            window.location.href = UTF8ToString($0);\
+        \0" };
+    }
+
+    pub fn location_assign(&self, s: &str) {
+        js! { (s) b"\
+            window.location.assign(UTF8ToString($0));\
+        \0" };
+    }
+
+    /// Navigates to `s` without adding a history entry.
+    pub fn location_replace(&self, s: &str) {
+        js! { (s) b"\
+            window.location.replace(UTF8ToString($0));\
+        \0" };
+    }
+
+    pub fn location_reload(&self) {
+        js! { b"\
+            window.location.reload();\
+        \0" };
+    }
+
+    /// The query string as decoded (key, value) pairs, in order. Keys
+    /// without `=` get an empty value.
+    pub fn location_query(&self) -> Vec<(String, String)> {
+        let pairs = js! { b"\
+            var decode = function (s) {\
+                s = s.replace(/\\+/g, ' ');\
+                try {\
+                    return decodeURIComponent(s);\
+                } catch (e) {\
+                    return s;\
+                }\
+            };\
+            var keys = [];\
+            var values = [];\
+            window.location.search.replace(/^\\?/, '').split('&').forEach(function (part) {\
+                if (!part) return;\
+                var i = part.indexOf('=');\
+                keys.push(decode(i < 0 ? part : part.slice(0, i)));\
+                values.push(i < 0 ? '' : decode(part.slice(i + 1)));\
+            });\
+            return WEBPLATFORM.rs_refs.push([keys, values]) - 1;\
+        \0" };
+        let keys = js! { (pairs) b"\
+            return WEBPLATFORM.rs_refs.push(WEBPLATFORM.rs_refs[$0][0]) - 1;\
+        \0" };
+        let values = js! { (pairs) b"\
+            return WEBPLATFORM.rs_refs.push(WEBPLATFORM.rs_refs[$0][1]) - 1;\
+        \0" };
+        string_list(keys).into_iter().zip(string_list(values)).collect()
+    }
+
+    /// Adds a history entry for `url` (or the current URL) without
+    /// navigating. `state` is handed back by `on_popstate`.
+    pub fn history_push_state(&self, state: &str, title: &str, url: Option<&str>) {
+        js! { (state, title, url.unwrap_or(""), url.is_some() as i32) b"\
+            window.history.pushState(UTF8ToString($0), UTF8ToString($1), $3 ? UTF8ToString($2) : undefined);\
+        \0" };
+    }
+
+    pub fn history_replace_state(&self, state: &str, title: &str, url: Option<&str>) {
+        js! { (state, title, url.unwrap_or(""), url.is_some() as i32) b"\
+            window.history.replaceState(UTF8ToString($0), UTF8ToString($1), $3 ? UTF8ToString($2) : undefined);\
+        \0" };
+    }
+
+    /// The state of the current entry, `None` if it wasn't set from Rust.
+    pub fn history_state(&self) -> Option<String> {
+        let a = js! { b"\
+            var str = window.history.state;\
+            if (typeof str != 'string') return -1;\
+            return allocate(intArrayFromString(str), 'i8', ALLOC_STACK);\
+        \0" };
+        if a == -1 {
+            None
+        } else {
+            Some(unsafe {
+                str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+            })
+        }
+    }
+
+    pub fn history_back(&self) {
+        js! { b"\
+            window.history.back();\
+        \0" };
+    }
+
+    pub fn history_forward(&self) {
+        js! { b"\
+            window.history.forward();\
+        \0" };
+    }
+
+    pub fn history_go(&self, delta: i32) {
+        js! { (delta) b"\
+            window.history.go($0);\
+        \0" };
+    }
+
+    pub fn history_length(&self) -> i32 {
+        js! { b"\
+            return window.history.length;\
+        \0" }
+    }
+
+    /// Calls `f` with the state of the entry navigated to whenever the user
+    /// moves through history.
+    pub fn on_popstate<F: FnMut(Option<String>) + 'a>(&self, mut f: F) {
+        self.on("popstate", move |_| {
+            let a = js! { b"\
+                var str = WEBPLATFORM.event.state;\
+                if (typeof str != 'string') return -1;\
+                return allocate(intArrayFromString(str), 'i8', ALLOC_STACK);\
+            \0" };
+            f(if a == -1 {
+                None
+            } else {
+                Some(unsafe {
+                    str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+                })
+            });
+        });
+    }
+
     pub fn on<F: FnMut(Event) + 'a>(&self, s: &str, f: F) {
         unsafe {
             let b = Box::new(f);
@@ -469,7 +1757,13 @@ impl<'a> Document<'a> {
                 &*self as *const _ as *const libc::c_void)
                 b"\
                 window.addEventListener(UTF8ToString($1), function (e) {\
-                    Runtime.dynCall('viii', $3, [$2, $4, e.target ? WEBPLATFORM.rs_refs.push(e.target) - 1 : -1]);\
+                    var outer = WEBPLATFORM.event;\
+                    WEBPLATFORM.event = e;\
+                    try {\
+                        Runtime.dynCall('viii', $3, [$2, $4, e.target ? WEBPLATFORM.rs_refs.push(e.target) - 1 : -1]);\
+                    } finally {\
+                        WEBPLATFORM.event = outer;\
+                    }\
                 }, false);\
             \0" };
             self.refs.borrow_mut().push(b);
@@ -513,6 +1807,13 @@ impl<'a> Document<'a> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentReadyState {
+    Loading,
+    Interactive,
+    Complete,
+}
+
 pub struct LocalStorageInterface;
 
 pub struct LocalStorageIterator {
@@ -600,6 +1901,7 @@ pub fn init<'a>() -> Document<'a> {
         console.log('hi');\
         window.WEBPLATFORM || (window.WEBPLATFORM = {\
             rs_refs: [],\
+            event: null,\
         });\
         \"use strict\";var range;var NS_XHTML=\"http://www.w3.org/1999/xhtml\";var doc=typeof document===\"undefined\"?undefined:document;var testEl=doc?doc.body||doc.createElement(\"div\"):{};var actualHasAttributeNS;if(testEl.hasAttributeNS){actualHasAttributeNS=function(el,namespaceURI,name){return el.hasAttributeNS(namespaceURI,name)}}else if(testEl.hasAttribute){actualHasAttributeNS=function(el,namespaceURI,name){return el.hasAttribute(name)}}else{actualHasAttributeNS=function(el,namespaceURI,name){return el.getAttributeNode(namespaceURI,name)!=null}}var hasAttributeNS=actualHasAttributeNS;function toElement(str){if(!range&&doc.createRange){range=doc.createRange();range.selectNode(doc.body)}var fragment;if(range&&range.createContextualFragment){fragment=range.createContextualFragment(str)}else{fragment=doc.createElement(\"body\");fragment.innerHTML=str}return fragment.childNodes[0]}function compareNodeNames(fromEl,toEl){var fromNodeName=fromEl.nodeName;var toNodeName=toEl.nodeName;if(fromNodeName===toNodeName){return true}if(toEl.actualize&&fromNodeName.charCodeAt(0)<91&&toNodeName.charCodeAt(0)>90){return fromNodeName===toNodeName.toUpperCase()}else{return false}}function createElementNS(name,namespaceURI){return!namespaceURI||namespaceURI===NS_XHTML?doc.createElement(name):doc.createElementNS(namespaceURI,name)}function moveChildren(fromEl,toEl){var curChild=fromEl.firstChild;while(curChild){var nextChild=curChild.nextSibling;toEl.appendChild(curChild);curChild=nextChild}return toEl}function morphAttrs(fromNode,toNode){var attrs=toNode.attributes;var i;var attr;var attrName;var attrNamespaceURI;var attrValue;var fromValue;for(i=attrs.length-1;i>=0;--i){attr=attrs[i];attrName=attr.name;attrNamespaceURI=attr.namespaceURI;attrValue=attr.value;if(attrNamespaceURI){attrName=attr.localName||attrName;fromValue=fromNode.getAttributeNS(attrNamespaceURI,attrName);if(fromValue!==attrValue){fromNode.setAttributeNS(attrNamespaceURI,attrName,attrValue)}}else{fromValue=fromNode.getAttribute(attrName);if(fromValue!==attrValue){fromNode.setAttribute(attrName,attrValue)}}}attrs=fromNode.attributes;for(i=attrs.length-1;i>=0;--i){attr=attrs[i];if(attr.specified!==false){attrName=attr.name;attrNamespaceURI=attr.namespaceURI;if(attrNamespaceURI){attrName=attr.localName||attrName;if(!hasAttributeNS(toNode,attrNamespaceURI,attrName)){fromNode.removeAttributeNS(attrNamespaceURI,attrName)}}else{if(!hasAttributeNS(toNode,null,attrName)){fromNode.removeAttribute(attrName)}}}}}function syncBooleanAttrProp(fromEl,toEl,name){if(fromEl[name]!==toEl[name]){fromEl[name]=toEl[name];if(fromEl[name]){fromEl.setAttribute(name,\"\")}else{fromEl.removeAttribute(name,\"\")}}}var specialElHandlers={OPTION:function(fromEl,toEl){syncBooleanAttrProp(fromEl,toEl,\"selected\")},INPUT:function(fromEl,toEl){syncBooleanAttrProp(fromEl,toEl,\"checked\");syncBooleanAttrProp(fromEl,toEl,\"disabled\");if(fromEl.value!==toEl.value){fromEl.value=toEl.value}if(!hasAttributeNS(toEl,null,\"value\")){fromEl.removeAttribute(\"value\")}},TEXTAREA:function(fromEl,toEl){var newValue=toEl.value;if(fromEl.value!==newValue){fromEl.value=newValue}if(fromEl.firstChild){if(newValue===\"\"&&fromEl.firstChild.nodeValue===fromEl.placeholder){return}fromEl.firstChild.nodeValue=newValue}},SELECT:function(fromEl,toEl){if(!hasAttributeNS(toEl,null,\"multiple\")){var selectedIndex=-1;var i=0;var curChild=toEl.firstChild;while(curChild){var nodeName=curChild.nodeName;if(nodeName&&nodeName.toUpperCase()===\"OPTION\"){if(hasAttributeNS(curChild,null,\"selected\")){selectedIndex=i;break}i++}curChild=curChild.nextSibling}fromEl.selectedIndex=i}}};var ELEMENT_NODE=1;var TEXT_NODE=3;var COMMENT_NODE=8;function noop(){}function defaultGetNodeKey(node){return node.id}function morphdomFactory(morphAttrs){return function morphdom(fromNode,toNode,options){if(!options){options={}}if(typeof toNode===\"string\"){if(fromNode.nodeName===\"#document\"||fromNode.nodeName===\"HTML\"){var toNodeHtml=toNode;toNode=doc.createElement(\"html\");toNode.innerHTML=toNodeHtml}else{toNode=toElement(toNode)}}var getNodeKey=options.getNodeKey||defaultGetNodeKey;var onBeforeNodeAdded=options.onBeforeNodeAdded||noop;var onNodeAdded=options.onNodeAdded||noop;var onBeforeElUpdated=options.onBeforeElUpdated||noop;var onElUpdated=options.onElUpdated||noop;var onBeforeNodeDiscarded=options.onBeforeNodeDiscarded||noop;var onNodeDiscarded=options.onNodeDiscarded||noop;var onBeforeElChildrenUpdated=options.onBeforeElChildrenUpdated||noop;var childrenOnly=options.childrenOnly===true;var fromNodesLookup={};var keyedRemovalList;function addKeyedRemoval(key){if(keyedRemovalList){keyedRemovalList.push(key)}else{keyedRemovalList=[key]}}function walkDiscardedChildNodes(node,skipKeyedNodes){if(node.nodeType===ELEMENT_NODE){var curChild=node.firstChild;while(curChild){var key=undefined;if(skipKeyedNodes&&(key=getNodeKey(curChild))){addKeyedRemoval(key)}else{onNodeDiscarded(curChild);if(curChild.firstChild){walkDiscardedChildNodes(curChild,skipKeyedNodes)}}curChild=curChild.nextSibling}}}function removeNode(node,parentNode,skipKeyedNodes){if(onBeforeNodeDiscarded(node)===false){return}if(parentNode){parentNode.removeChild(node)}onNodeDiscarded(node);walkDiscardedChildNodes(node,skipKeyedNodes)}function indexTree(node){if(node.nodeType===ELEMENT_NODE){var curChild=node.firstChild;while(curChild){var key=getNodeKey(curChild);if(key){fromNodesLookup[key]=curChild}indexTree(curChild);curChild=curChild.nextSibling}}}indexTree(fromNode);function handleNodeAdded(el){onNodeAdded(el);var curChild=el.firstChild;while(curChild){var nextSibling=curChild.nextSibling;var key=getNodeKey(curChild);if(key){var unmatchedFromEl=fromNodesLookup[key];if(unmatchedFromEl&&compareNodeNames(curChild,unmatchedFromEl)){curChild.parentNode.replaceChild(unmatchedFromEl,curChild);morphEl(unmatchedFromEl,curChild)}}handleNodeAdded(curChild);curChild=nextSibling}}function morphEl(fromEl,toEl,childrenOnly){var toElKey=getNodeKey(toEl);var curFromNodeKey;if(toElKey){delete fromNodesLookup[toElKey]}if(toNode.isSameNode&&toNode.isSameNode(fromNode)){return}if(!childrenOnly){if(onBeforeElUpdated(fromEl,toEl)===false){return}morphAttrs(fromEl,toEl);onElUpdated(fromEl);if(onBeforeElChildrenUpdated(fromEl,toEl)===false){return}}if(fromEl.nodeName!==\"TEXTAREA\"){var curToNodeChild=toEl.firstChild;var curFromNodeChild=fromEl.firstChild;var curToNodeKey;var fromNextSibling;var toNextSibling;var matchingFromEl;outer:while(curToNodeChild){toNextSibling=curToNodeChild.nextSibling;curToNodeKey=getNodeKey(curToNodeChild);while(curFromNodeChild){fromNextSibling=curFromNodeChild.nextSibling;if(curToNodeChild.isSameNode&&curToNodeChild.isSameNode(curFromNodeChild)){curToNodeChild=toNextSibling;curFromNodeChild=fromNextSibling;continue outer}curFromNodeKey=getNodeKey(curFromNodeChild);var curFromNodeType=curFromNodeChild.nodeType;var isCompatible=undefined;if(curFromNodeType===curToNodeChild.nodeType){if(curFromNodeType===ELEMENT_NODE){if(curToNodeKey){if(curToNodeKey!==curFromNodeKey){if(matchingFromEl=fromNodesLookup[curToNodeKey]){if(curFromNodeChild.nextSibling===matchingFromEl){isCompatible=false}else{fromEl.insertBefore(matchingFromEl,curFromNodeChild);fromNextSibling=curFromNodeChild.nextSibling;if(curFromNodeKey){addKeyedRemoval(curFromNodeKey)}else{removeNode(curFromNodeChild,fromEl,true)}curFromNodeChild=matchingFromEl}}else{isCompatible=false}}}else if(curFromNodeKey){isCompatible=false}isCompatible=isCompatible!==false&&compareNodeNames(curFromNodeChild,curToNodeChild);if(isCompatible){morphEl(curFromNodeChild,curToNodeChild)}}else if(curFromNodeType===TEXT_NODE||curFromNodeType==COMMENT_NODE){isCompatible=true;curFromNodeChild.nodeValue=curToNodeChild.nodeValue}}if(isCompatible){curToNodeChild=toNextSibling;curFromNodeChild=fromNextSibling;continue outer}if(curFromNodeKey){addKeyedRemoval(curFromNodeKey)}else{removeNode(curFromNodeChild,fromEl,true)}curFromNodeChild=fromNextSibling}if(curToNodeKey&&(matchingFromEl=fromNodesLookup[curToNodeKey])&&compareNodeNames(matchingFromEl,curToNodeChild)){fromEl.appendChild(matchingFromEl);morphEl(matchingFromEl,curToNodeChild)}else{var onBeforeNodeAddedResult=onBeforeNodeAdded(curToNodeChild);if(onBeforeNodeAddedResult!==false){if(onBeforeNodeAddedResult){curToNodeChild=onBeforeNodeAddedResult}if(curToNodeChild.actualize){curToNodeChild=curToNodeChild.actualize(fromEl.ownerDocument||doc)}fromEl.appendChild(curToNodeChild);handleNodeAdded(curToNodeChild)}}curToNodeChild=toNextSibling;curFromNodeChild=fromNextSibling}while(curFromNodeChild){fromNextSibling=curFromNodeChild.nextSibling;if(curFromNodeKey=getNodeKey(curFromNodeChild)){addKeyedRemoval(curFromNodeKey)}else{removeNode(curFromNodeChild,fromEl,true)}curFromNodeChild=fromNextSibling}}var specialElHandler=specialElHandlers[fromEl.nodeName];if(specialElHandler){specialElHandler(fromEl,toEl)}}var morphedNode=fromNode;var morphedNodeType=morphedNode.nodeType;var toNodeType=toNode.nodeType;if(!childrenOnly){if(morphedNodeType===ELEMENT_NODE){if(toNodeType===ELEMENT_NODE){if(!compareNodeNames(fromNode,toNode)){onNodeDiscarded(fromNode);morphedNode=moveChildren(fromNode,createElementNS(toNode.nodeName,toNode.namespaceURI))}}else{morphedNode=toNode}}else if(morphedNodeType===TEXT_NODE||morphedNodeType===COMMENT_NODE){if(toNodeType===morphedNodeType){morphedNode.nodeValue=toNode.nodeValue;return morphedNode}else{morphedNode=toNode}}}if(morphedNode===toNode){onNodeDiscarded(fromNode)}else{morphEl(morphedNode,toNode,childrenOnly);if(keyedRemovalList){for(var i=0,len=keyedRemovalList.length;i<len;i++){var elToRemove=fromNodesLookup[keyedRemovalList[i]];if(elToRemove){removeNode(elToRemove,elToRemove.parentNode,false)}}}}if(!childrenOnly&&morphedNode!==fromNode&&fromNode.parentNode){if(morphedNode.actualize){morphedNode=morphedNode.actualize(fromNode.ownerDocument||doc)}fromNode.parentNode.replaceChild(morphedNode,fromNode)}return morphedNode}}window.morphdom=morphdomFactory(morphAttrs);\
         console.log('Loaded morphdom: '+(typeof window.morphdom=='function'));\
@@ -629,3 +1931,59 @@ pub extern "C" fn syscall(a: i32) -> i32 {
     }
     return -1
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{data_key, data_attr, css_name, json_check};
+
+    #[test]
+    fn data_key_camelizes_lowercase_after_hyphen() {
+        assert_eq!(data_key("user-id"), "userId");
+        assert_eq!(data_key("a-long-name"), "aLongName");
+        assert_eq!(data_key("userId"), "userId");
+    }
+
+    #[test]
+    fn data_key_keeps_other_hyphens() {
+        assert_eq!(data_key("item-2"), "item-2");
+        assert_eq!(data_key("x-Y"), "x-Y");
+        assert_eq!(data_key("trailing-"), "trailing-");
+    }
+
+    #[test]
+    fn data_attr_kebabs_uppercase() {
+        assert_eq!(data_attr("userId"), "user-id");
+        assert_eq!(data_attr("item-2"), "item-2");
+    }
+
+    #[test]
+    fn data_key_attr_round_trip() {
+        for attr in &["user-id", "item-2", "a-b-c", "plain"] {
+            assert_eq!(data_attr(&data_key(attr)), *attr);
+        }
+        for key in &["userId", "item-2", "aBC", "plain"] {
+            assert_eq!(data_key(&data_attr(key)), *key);
+        }
+    }
+
+    #[test]
+    fn css_name_kebabs_all_but_custom_properties() {
+        assert_eq!(css_name("backgroundColor"), "background-color");
+        assert_eq!(css_name("background-color"), "background-color");
+        assert_eq!(css_name("--mainColor"), "--mainColor");
+    }
+
+    #[test]
+    fn json_check_accepts_json() {
+        for s in &["null", " [1, -2.5e3, \"a\\u00e9\\n\", {}] ", "{\"a\": {\"b\": [true, false]}}", "0"] {
+            assert_eq!(json_check(s), Ok(()), "{}", s);
+        }
+    }
+
+    #[test]
+    fn json_check_rejects_invalid_json() {
+        for s in &["", "{", "[1,]", "{'a': 1}", "01", "1.", "nul", "\"a", "[1] 2", "{\"a\" 1}"] {
+            assert!(json_check(s).is_err(), "{}", s);
+        }
+    }
+}
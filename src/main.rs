@@ -3,6 +3,7 @@ extern crate libc;
 
 fn main() {
     let document = webplatform::init();
+    webplatform::set_panic_hook();
     {
         let body = document.element_query("body").unwrap();
 
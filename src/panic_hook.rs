@@ -0,0 +1,72 @@
+use libc;
+use std::ffi::{CString, CStr};
+use std::str;
+use std::borrow::ToOwned;
+// Kept for the older toolchains the asm.js target needs; `PanicHookInfo`,
+// which replaces it, only exists since Rust 1.81.
+#[allow(deprecated)]
+use std::panic::{self, PanicInfo};
+
+use Interop;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicReport {
+    /// The panic message, with NUL bytes removed.
+    pub message: String,
+    /// `file:line:column` of the panic, if known.
+    pub location: Option<String>,
+    /// The JavaScript stack at the time of the panic.
+    pub stack: String,
+}
+
+#[allow(deprecated)]
+fn report(info: &PanicInfo) -> PanicReport {
+    let payload = info.payload();
+    let message = if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_owned()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Box<Any>".to_owned()
+    };
+    // `js!` passes strings as C strings, so a NUL would make it panic again.
+    let message = message.replace('\0', "");
+    let location = info.location().map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()).replace('\0', ""));
+
+    let a = js! { b"\
+        return allocate(intArrayFromString(new Error().stack || ''), 'i8', ALLOC_STACK);\
+    \0" };
+    let stack = unsafe {
+        str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+    };
+
+    PanicReport {
+        message: message,
+        location: location,
+        stack: stack,
+    }
+}
+
+fn log_report(report: &PanicReport) {
+    let location = report.location.as_ref().map(|l| &l[..]).unwrap_or("<unknown>");
+    js! { (&report.message[..], location, &report.stack[..]) b"\
+        console.error('panicked at ' + UTF8ToString($1) + ': ' + UTF8ToString($0) + '\\n\\nStack:\\n' + UTF8ToString($2));\
+    \0" };
+}
+
+/// Replaces the default panic hook with one that logs the panic message,
+/// its location and the JS stack to `console.error`.
+pub fn set_panic_hook() {
+    panic::set_hook(Box::new(|info| log_report(&report(info))));
+}
+
+/// Like `set_panic_hook`, then also calls `f`, e.g. to show the error in the page.
+pub fn set_panic_hook_with<F>(f: F)
+    where F: Fn(&PanicReport) + Send + Sync + 'static
+{
+    panic::set_hook(Box::new(move |info| {
+        let report = report(info);
+        log_report(&report);
+        f(&report);
+    }));
+}
@@ -0,0 +1,313 @@
+use libc;
+use std::ffi::{CString, CStr};
+use std::str;
+use std::borrow::ToOwned;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use {Interop, Document, percent_decode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouterMode {
+    /// Paths live in the fragment, e.g. `/app#/users/3`.
+    Hash,
+    /// Paths are real URLs, changed with `history_push_state`.
+    History,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    Rest,
+}
+
+type RouteHandler<'a> = Rc<RefCell<Box<dyn FnMut(HashMap<String, String>) + 'a>>>;
+type NotFoundHandler<'a> = Rc<RefCell<Box<dyn FnMut(String) + 'a>>>;
+
+struct Route<'a> {
+    pattern: Vec<Segment>,
+    handler: RouteHandler<'a>,
+}
+
+struct RouterState<'a> {
+    mode: RouterMode,
+    routes: RefCell<Vec<Route<'a>>>,
+    not_found: RefCell<Option<NotFoundHandler<'a>>>,
+    // Set while a handler runs; a dispatch from inside it is queued in
+    // `pending` and run once the handler returns.
+    dispatching: Cell<bool>,
+    pending: RefCell<Option<String>>,
+}
+
+/// Dispatches the current path to the first route whose pattern matches.
+///
+/// Patterns are `/`-separated; `:name` segments capture one path segment
+/// into the params under `name`, and a final `*` captures the rest of the
+/// path under `*`. Captured values are percent-decoded. A handler may
+/// navigate; the new path is dispatched once the handler returns.
+pub struct Router<'a> {
+    doc: *const Document<'a>,
+    state: Rc<RouterState<'a>>,
+}
+
+impl<'a> Clone for Router<'a> {
+    fn clone(&self) -> Router<'a> {
+        Router {
+            doc: self.doc,
+            state: self.state.clone(),
+        }
+    }
+}
+
+fn split_path(path: &str) -> Vec<&str> {
+    let end = path.find(|c| c == '?' || c == '#').unwrap_or(path.len());
+    path[..end].split('/').filter(|s| !s.is_empty()).collect()
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    split_path(pattern).into_iter().map(|s| {
+        if s == "*" {
+            Segment::Rest
+        } else if s.starts_with(':') {
+            Segment::Param(s[1..].to_owned())
+        } else {
+            Segment::Literal(s.to_owned())
+        }
+    }).collect()
+}
+
+fn match_path(pattern: &[Segment], path: &[&str]) -> Option<HashMap<String, String>> {
+    let mut params = HashMap::new();
+    for (i, segment) in pattern.iter().enumerate() {
+        match *segment {
+            Segment::Rest => {
+                params.insert("*".to_owned(), percent_decode(&path[i.min(path.len())..].join("/")));
+                return Some(params);
+            }
+            Segment::Param(ref name) => {
+                match path.get(i) {
+                    Some(value) => {
+                        params.insert(name.clone(), percent_decode(value));
+                    }
+                    None => return None,
+                }
+            }
+            Segment::Literal(ref lit) => {
+                if path.get(i) != Some(&&lit[..]) {
+                    return None;
+                }
+            }
+        }
+    }
+    if pattern.len() == path.len() {
+        Some(params)
+    } else {
+        None
+    }
+}
+
+impl<'a> Router<'a> {
+    pub fn new(doc: &Document<'a>, mode: RouterMode) -> Router<'a> {
+        Router {
+            doc: doc,
+            state: Rc::new(RouterState {
+                mode: mode,
+                routes: RefCell::new(Vec::new()),
+                not_found: RefCell::new(None),
+                dispatching: Cell::new(false),
+                pending: RefCell::new(None),
+            }),
+        }
+    }
+
+    pub fn route<F: FnMut(HashMap<String, String>) + 'a>(&self, pattern: &str, f: F) {
+        self.state.routes.borrow_mut().push(Route {
+            pattern: parse_pattern(pattern),
+            handler: Rc::new(RefCell::new(Box::new(f))),
+        });
+    }
+
+    /// Called with the path when no route matches.
+    pub fn not_found<F: FnMut(String) + 'a>(&self, f: F) {
+        *self.state.not_found.borrow_mut() = Some(Rc::new(RefCell::new(Box::new(f))));
+    }
+
+    /// The path the router currently sees, e.g. `/users/3`.
+    pub fn current_path(&self) -> String {
+        let a = match self.state.mode {
+            RouterMode::Hash => js! { b"\
+                var path = window.location.hash.replace(/^#!?/, '');\
+                return allocate(intArrayFromString(path.charAt(0) == '/' ? path : '/' + path), 'i8', ALLOC_STACK);\
+            \0" },
+            RouterMode::History => js! { b"\
+                var loc = window.location;\
+                return allocate(intArrayFromString(loc.pathname + loc.search), 'i8', ALLOC_STACK);\
+            \0" },
+        };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    /// Runs the handler for `path` without touching the URL. Called from
+    /// within a handler, it runs after that handler returns.
+    pub fn dispatch(&self, path: &str) {
+        if self.state.dispatching.get() {
+            *self.state.pending.borrow_mut() = Some(path.to_owned());
+            return;
+        }
+
+        self.state.dispatching.set(true);
+        let mut path = path.to_owned();
+        loop {
+            self.run_handler(&path);
+            match self.state.pending.borrow_mut().take() {
+                Some(next) => path = next,
+                None => break,
+            }
+        }
+        self.state.dispatching.set(false);
+    }
+
+    fn run_handler(&self, path: &str) {
+        let segments = split_path(path);
+        let found = self.state.routes.borrow().iter().filter_map(|route| {
+            match_path(&route.pattern, &segments).map(|params| (route.handler.clone(), params))
+        }).next();
+
+        match found {
+            Some((handler, params)) => (&mut *handler.borrow_mut())(params),
+            None => {
+                let not_found = self.state.not_found.borrow().clone();
+                if let Some(not_found) = not_found {
+                    (&mut *not_found.borrow_mut())(path.to_owned());
+                }
+            }
+        }
+    }
+
+    /// Changes the URL to `path` and runs its handler.
+    pub fn navigate(&self, path: &str) {
+        match self.state.mode {
+            RouterMode::Hash => {
+                // Setting the hash fires `hashchange`, which dispatches, but
+                // only if it changes.
+                let current = self.current_path();
+                if current == path || current[1..] == *path {
+                    self.dispatch(path);
+                } else {
+                    js! { (path) b"\
+                        window.location.hash = UTF8ToString($0);\
+                    \0" };
+                }
+            }
+            RouterMode::History => {
+                let doc = unsafe { &*self.doc };
+                doc.history_push_state("", "", Some(path));
+                self.dispatch(path);
+            }
+        }
+    }
+
+    /// Starts listening for URL changes, and in `History` mode for clicks
+    /// on same-origin links other than fragment links into the current
+    /// page, then dispatches the current path.
+    pub fn start(&self) {
+        let doc = unsafe { &*self.doc };
+        match self.state.mode {
+            RouterMode::Hash => {
+                let router = self.clone();
+                doc.on("hashchange", move |_| {
+                    let path = router.current_path();
+                    router.dispatch(&path);
+                });
+            }
+            RouterMode::History => {
+                let router = self.clone();
+                doc.on("popstate", move |_| {
+                    let path = router.current_path();
+                    router.dispatch(&path);
+                });
+
+                let router = self.clone();
+                doc.on("click", move |_| {
+                    let a = js! { b"\
+                        var e = WEBPLATFORM.event;\
+                        if (e.defaultPrevented || e.button !== 0 || e.metaKey || e.ctrlKey || e.shiftKey || e.altKey) {\
+                            return -1;\
+                        }\
+                        var link = e.target.closest ? e.target.closest('a[href]') : null;\
+                        if (!link || (link.target && link.target != '_self') || link.hasAttribute('download')) {\
+                            return -1;\
+                        }\
+                        var loc = window.location;\
+                        if (link.protocol + '//' + link.host != loc.protocol + '//' + loc.host) {\
+                            return -1;\
+                        }\
+                        if (link.hash && link.pathname == loc.pathname && link.search == loc.search) {\
+                            return -1;\
+                        }\
+                        e.preventDefault();\
+                        return allocate(intArrayFromString(link.pathname + link.search + link.hash), 'i8', ALLOC_STACK);\
+                    \0" };
+                    if a != -1 {
+                        let path = unsafe {
+                            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+                        };
+                        router.navigate(&path);
+                    }
+                });
+            }
+        }
+
+        let path = self.current_path();
+        self.dispatch(&path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_path, parse_pattern, match_path, Segment};
+
+    #[test]
+    fn split_path_drops_empty_segments_query_and_fragment() {
+        assert_eq!(split_path("/users//3/?tab=posts#top"), vec!["users", "3"]);
+        assert!(split_path("/").is_empty());
+    }
+
+    #[test]
+    fn parse_pattern_segments() {
+        assert_eq!(parse_pattern("/users/:id/*"), vec![
+            Segment::Literal("users".to_owned()),
+            Segment::Param("id".to_owned()),
+            Segment::Rest,
+        ]);
+    }
+
+    #[test]
+    fn match_path_captures_and_decodes_params() {
+        let pattern = parse_pattern("/users/:id");
+        let params = match_path(&pattern, &split_path("/users/john%20doe")).unwrap();
+        assert_eq!(params["id"], "john doe");
+        assert!(match_path(&pattern, &split_path("/users")).is_none());
+        assert!(match_path(&pattern, &split_path("/users/3/posts")).is_none());
+        assert!(match_path(&pattern, &split_path("/groups/3")).is_none());
+    }
+
+    #[test]
+    fn match_path_rest_takes_remaining_segments() {
+        let pattern = parse_pattern("/files/*");
+        let params = match_path(&pattern, &split_path("/files/a/b.txt")).unwrap();
+        assert_eq!(params["*"], "a/b.txt");
+        let params = match_path(&pattern, &split_path("/files")).unwrap();
+        assert_eq!(params["*"], "");
+    }
+
+    #[test]
+    fn match_path_root() {
+        assert!(match_path(&parse_pattern("/"), &split_path("/")).is_some());
+        assert!(match_path(&parse_pattern("/"), &split_path("/a")).is_none());
+    }
+}
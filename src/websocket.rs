@@ -0,0 +1,491 @@
+use libc;
+use std::ffi::{CString, CStr};
+use std::str;
+use std::borrow::ToOwned;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+
+use {Interop, Document, listen, exception_message};
+
+pub struct WebSocket<'a> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseEvent {
+    pub code: i32,
+    pub reason: String,
+    pub was_clean: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadyState {
+    Connecting,
+    Open,
+    Closing,
+    Closed,
+}
+
+impl<'a> WebSocket<'a> {
+    /// Fails if the browser rejects the URL or protocol list.
+    pub fn connect(doc: &Document<'a>, url: &str, protocols: &[&str]) -> Result<WebSocket<'a>, String> {
+        let list = js! { b"\
+            return WEBPLATFORM.rs_refs.push([]) - 1;\
+        \0" };
+        for protocol in protocols {
+            js! { (list, *protocol) b"\
+                WEBPLATFORM.rs_refs[$0].push(UTF8ToString($1));\
+            \0" };
+        }
+        let id = js! { (url, list) b"\
+            try {\
+                var ws = new WebSocket(UTF8ToString($0), WEBPLATFORM.rs_refs[$1]);\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            ws.binaryType = 'arraybuffer';\
+            return WEBPLATFORM.rs_refs.push(ws) - 1;\
+        \0" };
+
+        if id < 0 {
+            Err(exception_message(id))
+        } else {
+            Ok(WebSocket {
+                id: id,
+                doc: doc,
+            })
+        }
+    }
+
+    pub fn ready_state(&self) -> ReadyState {
+        let state = js! { (self.id) b"\
+            return WEBPLATFORM.rs_refs[$0].readyState;\
+        \0" };
+        match state {
+            0 => ReadyState::Connecting,
+            1 => ReadyState::Open,
+            2 => ReadyState::Closing,
+            _ => ReadyState::Closed,
+        }
+    }
+
+    /// The subprotocol selected by the server, empty if none.
+    pub fn protocol(&self) -> String {
+        let a = js! { (self.id) b"\
+            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].protocol), 'i8', ALLOC_STACK);\
+        \0" };
+        unsafe {
+            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+        }
+    }
+
+    /// Fails while the socket is still connecting.
+    pub fn send_text(&self, s: &str) -> Result<(), String> {
+        let err = js! { (self.id, s) b"\
+            try {\
+                WEBPLATFORM.rs_refs[$0].send(UTF8ToString($1));\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return 0;\
+        \0" };
+        if err < 0 {
+            Err(exception_message(err))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Fails while the socket is still connecting.
+    pub fn send_binary(&self, b: &[u8]) -> Result<(), String> {
+        let err = js! { (self.id, b.as_ptr() as *const libc::c_void, b.len() as i32) b"\
+            try {\
+                WEBPLATFORM.rs_refs[$0].send(HEAPU8.slice($1, $1 + $2));\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return 0;\
+        \0" };
+        if err < 0 {
+            Err(exception_message(err))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// `code` must be 1000 or in the range 3000-4999, and `reason` at most
+    /// 123 bytes.
+    pub fn close(&self, code: i32, reason: &str) -> Result<(), String> {
+        close_code_check(code)?;
+        let err = js! { (self.id, code, reason) b"\
+            try {\
+                WEBPLATFORM.rs_refs[$0].close($1, UTF8ToString($2));\
+            } catch (e) {\
+                return -2 - (WEBPLATFORM.rs_refs.push(e) - 1);\
+            }\
+            return 0;\
+        \0" };
+        if err < 0 {
+            Err(exception_message(err))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn on_open<F: FnMut() + 'a>(&self, mut f: F) {
+        listen(self.doc, self.id, "open", false, move |_| f());
+    }
+
+    pub fn on_message<F: FnMut(Message) + 'a>(&self, mut f: F) {
+        listen(self.doc, self.id, "message", false, move |_| {
+            let len = js! { b"\
+                var data = WEBPLATFORM.event.data;\
+                return typeof data === 'string' ? -1 : data.byteLength;\
+            \0" };
+            f(if len < 0 {
+                let a = js! { b"\
+                    return allocate(intArrayFromString(WEBPLATFORM.event.data), 'i8', ALLOC_STACK);\
+                \0" };
+                Message::Text(unsafe {
+                    str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+                })
+            } else {
+                let mut buf = vec![0u8; len as usize];
+                js! { (buf.as_mut_ptr() as *const libc::c_void) b"\
+                    HEAPU8.set(new Uint8Array(WEBPLATFORM.event.data), $0);\
+                \0" };
+                Message::Binary(buf)
+            });
+        });
+    }
+
+    pub fn on_close<F: FnMut(CloseEvent) + 'a>(&self, mut f: F) {
+        listen(self.doc, self.id, "close", false, move |_| {
+            let code = js! { b"\
+                return WEBPLATFORM.event.code;\
+            \0" };
+            let was_clean = js! { b"\
+                return +WEBPLATFORM.event.wasClean;\
+            \0" };
+            let a = js! { b"\
+                return allocate(intArrayFromString(WEBPLATFORM.event.reason), 'i8', ALLOC_STACK);\
+            \0" };
+            f(CloseEvent {
+                code: code,
+                reason: unsafe {
+                    str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
+                },
+                was_clean: was_clean != 0,
+            });
+        });
+    }
+
+    /// The browser gives no detail on WebSocket errors; a `close` always follows.
+    pub fn on_error<F: FnMut() + 'a>(&self, mut f: F) {
+        listen(self.doc, self.id, "error", false, move |_| f());
+    }
+}
+
+fn close_code_check(code: i32) -> Result<(), String> {
+    if code != 1000 && !(3000..=4999).contains(&code) {
+        return Err(format!("InvalidAccessError: close code {} is not 1000 or in 3000-4999", code));
+    }
+    Ok(())
+}
+
+/// A message socket: a browser `WebSocket`, or a `LocalEchoSocket` in tests.
+/// In both, handlers run after the call that triggered them returns.
+pub trait Socket<'a> {
+    fn ready_state(&self) -> ReadyState;
+    fn send_text(&self, s: &str) -> Result<(), String>;
+    fn send_binary(&self, b: &[u8]) -> Result<(), String>;
+    fn close(&self, code: i32, reason: &str) -> Result<(), String>;
+    fn on_open<F: FnMut() + 'a>(&self, f: F);
+    fn on_message<F: FnMut(Message) + 'a>(&self, f: F);
+    fn on_close<F: FnMut(CloseEvent) + 'a>(&self, f: F);
+}
+
+impl<'a> Socket<'a> for WebSocket<'a> {
+    fn ready_state(&self) -> ReadyState {
+        WebSocket::ready_state(self)
+    }
+
+    fn send_text(&self, s: &str) -> Result<(), String> {
+        WebSocket::send_text(self, s)
+    }
+
+    fn send_binary(&self, b: &[u8]) -> Result<(), String> {
+        WebSocket::send_binary(self, b)
+    }
+
+    fn close(&self, code: i32, reason: &str) -> Result<(), String> {
+        WebSocket::close(self, code, reason)
+    }
+
+    fn on_open<F: FnMut() + 'a>(&self, f: F) {
+        WebSocket::on_open(self, f)
+    }
+
+    fn on_message<F: FnMut(Message) + 'a>(&self, f: F) {
+        WebSocket::on_message(self, f)
+    }
+
+    fn on_close<F: FnMut(CloseEvent) + 'a>(&self, f: F) {
+        WebSocket::on_close(self, f)
+    }
+}
+
+enum LocalEvent {
+    Open,
+    Message(Message),
+    Close(CloseEvent),
+}
+
+type LocalHandler<'a, T> = Box<dyn FnMut(T) + 'a>;
+
+// Handlers may register more handlers, so the list isn't borrowed while
+// they run.
+fn run_handlers<'a, T: Clone>(handlers: &RefCell<Vec<LocalHandler<'a, T>>>, value: T) {
+    let mut running = handlers.replace(Vec::new());
+    for f in running.iter_mut() {
+        f(value.clone());
+    }
+    let added = handlers.replace(running);
+    handlers.borrow_mut().extend(added);
+}
+
+/// An in-memory stand-in for a `WebSocket` connected to an echo server, for
+/// tests that run outside the browser. Events are queued and delivered by
+/// `flush`: first `open`, then each message sent back, then `close`.
+pub struct LocalEchoSocket<'a> {
+    state: Cell<ReadyState>,
+    pending: RefCell<VecDeque<LocalEvent>>,
+    open: RefCell<Vec<LocalHandler<'a, ()>>>,
+    message: RefCell<Vec<LocalHandler<'a, Message>>>,
+    close: RefCell<Vec<LocalHandler<'a, CloseEvent>>>,
+}
+
+impl<'a> LocalEchoSocket<'a> {
+    pub fn new() -> LocalEchoSocket<'a> {
+        let mut pending = VecDeque::new();
+        pending.push_back(LocalEvent::Open);
+        LocalEchoSocket {
+            state: Cell::new(ReadyState::Connecting),
+            pending: RefCell::new(pending),
+            open: RefCell::new(Vec::new()),
+            message: RefCell::new(Vec::new()),
+            close: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Delivers every queued event, returning how many there were. Events
+    /// caused by the handlers wait for the next `flush`.
+    pub fn flush(&self) -> usize {
+        let pending = self.pending.replace(VecDeque::new());
+        let count = pending.len();
+        for event in pending {
+            match event {
+                LocalEvent::Open => {
+                    self.state.set(ReadyState::Open);
+                    run_handlers(&self.open, ());
+                }
+                LocalEvent::Message(message) => run_handlers(&self.message, message),
+                LocalEvent::Close(close) => {
+                    self.state.set(ReadyState::Closed);
+                    run_handlers(&self.close, close);
+                }
+            }
+        }
+        count
+    }
+
+    fn send(&self, message: Message) -> Result<(), String> {
+        match self.state.get() {
+            ReadyState::Connecting => Err("InvalidStateError: still in CONNECTING state".to_owned()),
+            ReadyState::Open => {
+                self.pending.borrow_mut().push_back(LocalEvent::Message(message));
+                Ok(())
+            }
+            // Like the browser, drops messages once closing.
+            ReadyState::Closing | ReadyState::Closed => Ok(()),
+        }
+    }
+}
+
+impl<'a> Default for LocalEchoSocket<'a> {
+    fn default() -> LocalEchoSocket<'a> {
+        LocalEchoSocket::new()
+    }
+}
+
+impl<'a> Socket<'a> for LocalEchoSocket<'a> {
+    fn ready_state(&self) -> ReadyState {
+        self.state.get()
+    }
+
+    fn send_text(&self, s: &str) -> Result<(), String> {
+        self.send(Message::Text(s.to_owned()))
+    }
+
+    fn send_binary(&self, b: &[u8]) -> Result<(), String> {
+        self.send(Message::Binary(b.to_vec()))
+    }
+
+    fn close(&self, code: i32, reason: &str) -> Result<(), String> {
+        close_code_check(code)?;
+        if reason.len() > 123 {
+            return Err("SyntaxError: close reason is longer than 123 bytes".to_owned());
+        }
+        let was_clean = match self.state.get() {
+            ReadyState::Closing | ReadyState::Closed => return Ok(()),
+            ReadyState::Connecting => false,
+            ReadyState::Open => true,
+        };
+        self.state.set(ReadyState::Closing);
+        let mut pending = self.pending.borrow_mut();
+        if !was_clean {
+            pending.clear();
+        }
+        pending.push_back(LocalEvent::Close(CloseEvent {
+            code: if was_clean { code } else { 1006 },
+            reason: if was_clean { reason.to_owned() } else { String::new() },
+            was_clean: was_clean,
+        }));
+        Ok(())
+    }
+
+    fn on_open<F: FnMut() + 'a>(&self, mut f: F) {
+        self.open.borrow_mut().push(Box::new(move |()| f()));
+    }
+
+    fn on_message<F: FnMut(Message) + 'a>(&self, f: F) {
+        self.message.borrow_mut().push(Box::new(f));
+    }
+
+    fn on_close<F: FnMut(CloseEvent) + 'a>(&self, f: F) {
+        self.close.borrow_mut().push(Box::new(f));
+    }
+}
+
+#[cfg(feature = "futures-core")]
+pub use self::stream::MessageStream;
+
+#[cfg(feature = "futures-core")]
+mod stream {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context, Poll, Waker};
+    use futures_core::Stream;
+
+    use super::{WebSocket, Message};
+
+    struct StreamState {
+        queue: VecDeque<Message>,
+        closed: bool,
+        waker: Option<Waker>,
+    }
+
+    impl StreamState {
+        fn wake(state: &Rc<RefCell<StreamState>>) {
+            let waker = state.borrow_mut().waker.take();
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+
+    /// The messages received by a `WebSocket`, ending when it closes.
+    pub struct MessageStream {
+        state: Rc<RefCell<StreamState>>,
+    }
+
+    impl Stream for MessageStream {
+        type Item = Message;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Message>> {
+            let mut state = self.state.borrow_mut();
+            match state.queue.pop_front() {
+                Some(message) => Poll::Ready(Some(message)),
+                None if state.closed => Poll::Ready(None),
+                None => {
+                    state.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    impl<'a> WebSocket<'a> {
+        /// Messages received from now on, as a `Stream`. Messages are
+        /// buffered until polled.
+        pub fn messages(&self) -> MessageStream {
+            let state = Rc::new(RefCell::new(StreamState {
+                queue: VecDeque::new(),
+                closed: false,
+                waker: None,
+            }));
+            let on_message = state.clone();
+            self.on_message(move |message| {
+                on_message.borrow_mut().queue.push_back(message);
+                StreamState::wake(&on_message);
+            });
+            let on_close = state.clone();
+            self.on_close(move |_| {
+                on_close.borrow_mut().closed = true;
+                StreamState::wake(&on_close);
+            });
+            MessageStream {
+                state: state,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::{CloseEvent, LocalEchoSocket, Message, ReadyState, Socket};
+
+    #[test]
+    fn local_echo_socket_echoes_until_closed() {
+        let socket = Rc::new(LocalEchoSocket::new());
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        assert!(socket.send_text("early").is_err());
+
+        let s = socket.clone();
+        socket.on_open(move || {
+            s.send_text("hi").unwrap();
+            s.send_binary(&[1, 2]).unwrap();
+        });
+        let s = seen.clone();
+        socket.on_message(move |message| s.borrow_mut().push(message));
+        let closed = Rc::new(RefCell::new(None));
+        let c = closed.clone();
+        socket.on_close(move |event| *c.borrow_mut() = Some(event));
+
+        assert_eq!(socket.flush(), 1);
+        assert_eq!(socket.ready_state(), ReadyState::Open);
+        assert!(seen.borrow().is_empty());
+        assert_eq!(socket.flush(), 2);
+        assert_eq!(*seen.borrow(), vec![Message::Text("hi".to_owned()), Message::Binary(vec![1, 2])]);
+
+        assert!(socket.close(1001, "").is_err());
+        socket.close(1000, "done").unwrap();
+        socket.send_text("dropped").unwrap();
+        assert_eq!(socket.ready_state(), ReadyState::Closing);
+        assert_eq!(socket.flush(), 1);
+        assert_eq!(socket.ready_state(), ReadyState::Closed);
+        assert_eq!(seen.borrow().len(), 2);
+        assert_eq!(*closed.borrow(), Some(CloseEvent { code: 1000, reason: "done".to_owned(), was_clean: true }));
+    }
+}